@@ -0,0 +1,228 @@
+//! 盤面を横長の帯に分けて、帯ごとに別のスレッドで世代を進めます。
+//! スレッドは世代ごとに作り直さず、Board が持っている間ずっと使い回します。
+
+use crate::{
+    rule::{self, Rule},
+    tiles::TILE_SIZE,
+    Cell, Topology,
+};
+use std::{
+    sync::{
+        mpsc::{self, Sender},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+};
+
+/// 1つの帯を1世代進める仕事です。帯の行は、計算の間だけスレッドに渡します。
+pub struct Job {
+    /// 帯の最初の行が盤面の何行目か(0 から数える)
+    pub top: usize,
+    /// 帯の行(外周の番兵の列も含む)
+    pub rows: Vec<Vec<Cell>>,
+    /// 帯のすぐ上と下の行(のりしろ)の生死
+    pub above: Vec<bool>,
+    pub below: Vec<bool>,
+    pub rule: Rule,
+    pub topology: Topology,
+    /// 計算が必要なタイル
+    pub active: Arc<Vec<bool>>,
+    /// タイルの横の数
+    pub tile_columns: usize,
+}
+
+/// 帯を1世代進めた結果です。
+pub struct Done {
+    pub top: usize,
+    pub rows: Vec<Vec<Cell>>,
+    /// 変化したセルを含むタイル
+    pub dirty: Vec<usize>,
+    /// (生まれたセル, 死んだセル) の数
+    pub changes: (u64, u64),
+}
+
+impl Job {
+    /// 計算が必要なタイルのセルだけを数えて、次の世代の状態にします。
+    fn run(self) -> Done {
+        let Job {
+            top,
+            mut rows,
+            above,
+            below,
+            rule,
+            topology,
+            active,
+            tile_columns,
+        } = self;
+        let active: &[bool] = &active;
+        let width = rows[0].len() - 2;
+        let height = rows.len();
+        let column = |x: usize, dx: usize| match topology {
+            Topology::Bounded => x + dx - 1,
+            Topology::Torus => (x + dx + width - 2) % width + 1,
+        };
+        // 帯の i 行目の、計算が必要な列の範囲
+        let spans = |i: usize| {
+            let ty = (top + i) / TILE_SIZE;
+            (0..tile_columns)
+                .filter(move |&tx| active[ty * tile_columns + tx])
+                .map(move |tx| (tx, tx * TILE_SIZE + 1..width.min((tx + 1) * TILE_SIZE) + 1))
+        };
+        // 先に帯の全セルを数えてから状態を変える。のりしろは数える前の状態を写したもの
+        for i in 0..height {
+            let mask = rule.mask(top + i);
+            for (_, xs) in spans(i) {
+                for x in xs {
+                    let neighbourhood = rule::neighbourhood(|dx, dy| {
+                        let x = column(x, (dx + 1) as usize);
+                        match i as isize + dy {
+                            -1 => above[x],
+                            y if y == height as isize => below[x],
+                            y => rows[y as usize][x].is_live(),
+                        }
+                    });
+                    rows[i][x].neighbourhood = neighbourhood & mask;
+                }
+            }
+        }
+        let mut dirty = Vec::new();
+        let mut changes = (0, 0);
+        for (i, row) in rows.iter_mut().enumerate() {
+            let ty = (top + i) / TILE_SIZE;
+            for (tx, xs) in spans(i) {
+                let mut changed = false;
+                for x in xs {
+                    let cell = &mut row[x];
+                    let (was, was_live) = (cell.now_state, cell.is_live());
+                    cell.commit_state(&rule);
+                    changed |= cell.now_state != was;
+                    match (was_live, cell.is_live()) {
+                        (false, true) => changes.0 += 1,
+                        (true, false) => changes.1 += 1,
+                        _ => {}
+                    }
+                }
+                if changed {
+                    dirty.push(ty * tile_columns + tx);
+                }
+            }
+        }
+        dirty.sort_unstable();
+        dirty.dedup();
+        Done {
+            top,
+            rows,
+            dirty,
+            changes,
+        }
+    }
+}
+
+/// 帯を計算するスレッドの集まりです。
+pub struct Bands {
+    /// スレッドごとの、仕事と結果の返し先の送り口
+    jobs: Vec<Sender<(Job, Sender<Done>)>>,
+    workers: Vec<JoinHandle<()>>,
+}
+impl Bands {
+    /// threads 個のスレッドを起動します。
+    pub fn new(threads: usize) -> Self {
+        let (jobs, workers) = (0..threads)
+            .map(|_| {
+                let (sender, receiver) = mpsc::channel::<(Job, Sender<Done>)>();
+                let worker = thread::spawn(move || {
+                    for (job, done) in receiver {
+                        // 受け取る側がいなくなっていても、次の仕事は続けて受け付ける
+                        let _ = done.send(job.run());
+                    }
+                });
+                (sender, worker)
+            })
+            .unzip();
+        Bands { jobs, workers }
+    }
+    /// スレッドの数を返します。
+    pub fn threads(&self) -> usize {
+        self.jobs.len()
+    }
+    /// 帯ごとの仕事をスレッドに割り振り、すべての結果を返します。結果の順番は決まっていません。
+    pub fn run(&self, jobs: Vec<Job>) -> Vec<Done> {
+        let count = jobs.len();
+        // 結果の返し先は仕事と一緒に渡すので、スレッドが異常終了すると受け取りが失敗して分かる
+        let (sender, done) = mpsc::channel();
+        for (i, job) in jobs.into_iter().enumerate() {
+            self.jobs[i % self.jobs.len()]
+                .send((job, sender.clone()))
+                .expect("帯の計算中にスレッドが異常終了しました");
+        }
+        drop(sender);
+        (0..count)
+            .map(|_| done.recv().expect("帯の計算中にスレッドが異常終了しました"))
+            .collect()
+    }
+}
+impl Drop for Bands {
+    fn drop(&mut self) {
+        // 仕事の送り口を閉じると、各スレッドのループが終わる
+        self.jobs.clear();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        soup::{Region, Soup, Symmetry},
+        Board, Topology,
+    };
+
+    /// 同じスープを1スレッドと threads スレッドで進め、毎世代の盤面と誕生・死亡数が一致することを確かめます。
+    fn assert_same_as_serial(rule: &str, topology: Topology, threads: usize) {
+        let (width, height) = (50, 37);
+        let soup = Soup {
+            density: 0.35,
+            region: Region::Centered {
+                width: 30,
+                height: 20,
+            },
+            symmetry: Symmetry::C1,
+            seed: 7,
+        };
+        let new_board = || {
+            let mut board = Board::new(width, height, 1);
+            board.set_rule(rule.parse().unwrap());
+            board.set_topology(topology);
+            board.set_live(soup.cells(width, height));
+            board
+        };
+        let (mut serial, mut parallel) = (new_board(), new_board());
+        parallel.set_threads(threads);
+        for generation in 0..60 {
+            serial.step();
+            parallel.step();
+            assert_eq!(
+                parallel.snapshot(),
+                serial.snapshot(),
+                "{} {:?} の {} 世代目",
+                rule,
+                topology,
+                generation
+            );
+            assert_eq!(parallel.births_and_deaths(), serial.births_and_deaths());
+        }
+    }
+
+    #[test]
+    fn matches_serial_step() {
+        for topology in [Topology::Bounded, Topology::Torus] {
+            for threads in [2, 3, 8] {
+                assert_same_as_serial("B3/S23", topology, threads);
+                assert_same_as_serial("345/2/4", topology, threads);
+            }
+        }
+        assert_same_as_serial("B2/S34H", Topology::Bounded, 3);
+        assert_same_as_serial("B2/S013V", Topology::Bounded, 3);
+    }
+}
@@ -0,0 +1,323 @@
+use crate::{
+    cycle::{self, Cycle, CycleDetector, Snapshot},
+    pattern::Pattern,
+    rule::{self, Rule},
+    summary, Topology,
+};
+
+/// 1セルを1ビットで持つ、固定サイズの盤面です。
+/// 1行を 64 セルずつ u64 に詰め、周囲の生存セルの数を全加算器の論理演算で 64 セル同時に数えます。
+/// 盤面の端の扱いも含め、Board と同じ結果になります。
+pub struct BitBoard {
+    /// 行ごとに words_per_row 個ずつ並べたセル。x 列目は x / 64 番目の語の x % 64 ビット目
+    cells: Vec<u64>,
+    words_per_row: usize,
+    width: usize,
+    height: usize,
+    old_boards: CycleDetector,
+    generation: u64,
+    rule: Rule,
+    /// 周囲の生存セルの数ごとに、死んだセルが生まれるか
+    birth: [bool; 9],
+    /// 周囲の生存セルの数ごとに、生存セルが生き残るか
+    survival: [bool; 9],
+    topology: Topology,
+    /// 直前の1世代で (生まれたセル, 死んだセル) の数
+    changes: (u64, u64),
+}
+impl BitBoard {
+    pub fn new(width: usize, height: usize, board_histories: usize) -> Self {
+        let words_per_row = width.div_ceil(64);
+        let mut board = BitBoard {
+            cells: vec![0; words_per_row * height],
+            words_per_row,
+            width,
+            height,
+            old_boards: CycleDetector::new(board_histories),
+            generation: 0,
+            rule: Rule::default(),
+            birth: [false; 9],
+            survival: [false; 9],
+            topology: Topology::Bounded,
+            changes: (0, 0),
+        };
+        board.set_rule(Rule::default());
+        board
+    }
+    /// 盤面の端の扱いを設定します。
+    pub fn set_topology(&mut self, topology: Topology) {
+        self.topology = topology;
+    }
+    /// points のセルを生きている状態にします。
+    pub fn set_live(&mut self, points: impl IntoIterator<Item = (usize, usize)>) {
+        for (x, y) in points {
+            self.cells[y * self.words_per_row + x / 64] |= 1 << (x % 64);
+        }
+    }
+    /// 世代交代に使うルールを設定します。
+    pub fn set_rule(&mut self, rule: Rule) {
+        // 生存セルの数だけで決まるルールにしか使わないので、数ごとに並びを1つ選んで調べれば足りる
+        for count in 0..=8 {
+            let neighbours = rule::first_neighbours(count);
+            self.birth[count] = rule.next_state(neighbours);
+            self.survival[count] = rule.next_state(neighbours | rule::CENTER);
+        }
+        self.rule = rule;
+    }
+    fn is_live(&self, x: usize, y: usize) -> bool {
+        self.cells[y * self.words_per_row + x / 64] >> (x % 64) & 1 == 1
+    }
+    /// 盤面全体をパターンとして取り出します。
+    pub fn to_pattern(&self) -> Pattern {
+        let mut cells = Vec::new();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if self.is_live(x, y) {
+                    cells.push((x, y));
+                }
+            }
+        }
+        Pattern {
+            width: self.width,
+            height: self.height,
+            rule: Some(self.rule.clone()),
+            cells,
+            dying: Vec::new(),
+            comments: Vec::new(),
+        }
+    }
+    /// y 行目を返します。盤面の外の行は、端の扱いに合わせて反対側の行か空の行になります。
+    fn row(&self, y: isize) -> Option<&[u64]> {
+        let height = self.height as isize;
+        let y = match self.topology {
+            Topology::Bounded if !(0..height).contains(&y) => return None,
+            Topology::Bounded => y,
+            Topology::Torus => y.rem_euclid(height),
+        } as usize;
+        Some(&self.cells[y * self.words_per_row..(y + 1) * self.words_per_row])
+    }
+    /// row を、各ビットが左隣のセルになるようにずらします。
+    fn west(&self, row: &[u64], out: &mut [u64]) {
+        let mut carry = 0;
+        for (o, &word) in out.iter_mut().zip(row) {
+            *o = word << 1 | carry;
+            carry = word >> 63;
+        }
+        if self.topology == Topology::Torus {
+            let last = self.width - 1;
+            out[0] |= row[last / 64] >> (last % 64) & 1;
+        }
+    }
+    /// row を、各ビットが右隣のセルになるようにずらします。
+    fn east(&self, row: &[u64], out: &mut [u64]) {
+        let mut carry = 0;
+        for (o, &word) in out.iter_mut().zip(row).rev() {
+            *o = word >> 1 | carry;
+            carry = word << 63;
+        }
+        if self.topology == Topology::Torus {
+            let last = self.width - 1;
+            out[last / 64] |= (row[0] & 1) << (last % 64);
+        }
+    }
+    /// 1世代進めます。
+    pub fn step(&mut self) {
+        let (shape, offset) = self.shape();
+        self.old_boards.record(self.generation, shape, offset);
+        self.generation += 1;
+        let words = self.words_per_row;
+        // 最後の語のうち、盤面の幅を超えるビットは常に 0 にしておく
+        let last_mask = match self.width % 64 {
+            0 => !0,
+            n => (1 << n) - 1,
+        };
+        let empty = vec![0; words];
+        let mut next = vec![0; self.cells.len()];
+        // 上・同じ・下の行と、それぞれを左右にずらしたもの
+        let mut shifted = vec![vec![0; words]; 6];
+        for y in 0..self.height {
+            let rows = [
+                self.row(y as isize - 1).unwrap_or(&empty),
+                self.row(y as isize).unwrap_or(&empty),
+                self.row(y as isize + 1).unwrap_or(&empty),
+            ];
+            for (i, row) in rows.iter().enumerate() {
+                self.west(row, &mut shifted[i * 2]);
+                self.east(row, &mut shifted[i * 2 + 1]);
+            }
+            for w in 0..words {
+                let neighbours = [
+                    shifted[0][w],
+                    rows[0][w],
+                    shifted[1][w],
+                    shifted[2][w],
+                    shifted[3][w],
+                    shifted[4][w],
+                    rows[2][w],
+                    shifted[5][w],
+                ];
+                let counts = count_neighbours(neighbours);
+                let alive = rows[1][w];
+                let mut word = 0;
+                for count in 0..=8 {
+                    let matched = matches_count(&counts, count);
+                    if self.birth[count] {
+                        word |= matched & !alive;
+                    }
+                    if self.survival[count] {
+                        word |= matched & alive;
+                    }
+                }
+                if w == words - 1 {
+                    word &= last_mask;
+                }
+                next[y * words + w] = word;
+            }
+        }
+        self.changes =
+            self.cells
+                .iter()
+                .zip(&next)
+                .fold((0, 0), |(births, deaths), (&old, &new)| {
+                    (
+                        births + (new & !old).count_ones() as u64,
+                        deaths + (old & !new).count_ones() as u64,
+                    )
+                });
+        self.cells = next;
+    }
+    /// 直前の1世代で (生まれたセル, 死んだセル) の数を返します。
+    pub fn births_and_deaths(&self) -> (u64, u64) {
+        self.changes
+    }
+    /// 盤面の状態のハッシュ値を返します。
+    pub fn state_hash(&self) -> u64 {
+        Snapshot(self.cells.clone()).to_hash()
+    }
+    pub fn show_board(&self) {
+        // Board と同じく、外周の番兵セルの分も空白で表示する
+        let border = format!("[{}]", " ".repeat(self.width + 2));
+        println!("{}", border);
+        for y in 0..self.height {
+            let row: String = (0..self.width)
+                .map(|x| if self.is_live(x, y) { '*' } else { ' ' })
+                .collect();
+            println!("[ {} ]", row);
+        }
+        println!("{}", border);
+        println!("======================================");
+    }
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+    /// 生存セルの数を返します。
+    pub fn population(&self) -> u64 {
+        self.cells.iter().map(|word| word.count_ones() as u64).sum()
+    }
+    /// 生存セルを囲む最小の矩形 (min_x, min_y, max_x, max_y) を返します。
+    pub fn bounding_box(&self) -> Option<(i64, i64, i64, i64)> {
+        summary::bounding_box((0..self.height).flat_map(|y| {
+            (0..self.width)
+                .filter(move |&x| self.is_live(x, y))
+                .map(move |x| (x as i64, y as i64))
+        }))
+    }
+    /// 繰り返しの判定に使う盤面の状態と、その位置を返します。
+    /// Board と同じく、端がつながっている盤面でだけ生存セルを左上に寄せます。
+    fn shape(&self) -> (Snapshot, (i64, i64)) {
+        match self.topology {
+            Topology::Bounded => (Snapshot(self.cells.clone()), (0, 0)),
+            Topology::Torus => {
+                cycle::torus_shape(self.width, self.height, 1, |x, y| self.is_live(x, y) as u8)
+            }
+        }
+    }
+    /// 以前に現れた盤面に戻っていれば、その繰り返しを返します。
+    pub fn cycle(&self) -> Option<Cycle> {
+        let (shape, offset) = self.shape();
+        self.old_boards
+            .find(self.generation, &shape, offset)
+            .map(|cycle| cycle.wrapped(self.width as i64, self.height as i64))
+    }
+}
+
+/// 全加算器です。(和, 桁上がり) を返します。
+fn full_adder(a: u64, b: u64, c: u64) -> (u64, u64) {
+    let ab = a ^ b;
+    (ab ^ c, a & b | ab & c)
+}
+
+/// 8つの隣のセルのビットを足し合わせ、周囲の生存セルの数を 4 ビット(1, 2, 4, 8 の位)で返します。
+fn count_neighbours(n: [u64; 8]) -> [u64; 4] {
+    let (sum_a, carry_a) = full_adder(n[0], n[1], n[2]);
+    let (sum_b, carry_b) = full_adder(n[3], n[4], n[5]);
+    let (sum_c, carry_c) = (n[6] ^ n[7], n[6] & n[7]);
+    // 1 の位
+    let (ones, carry_d) = full_adder(sum_a, sum_b, sum_c);
+    // 2 の位
+    let (twos_partial, carry_e) = full_adder(carry_a, carry_b, carry_c);
+    let (twos, carry_f) = (twos_partial ^ carry_d, twos_partial & carry_d);
+    // 4 と 8 の位
+    [ones, twos, carry_e ^ carry_f, carry_e & carry_f]
+}
+
+/// 周囲の生存セルの数がちょうど count のセルのビットを返します。
+fn matches_count(counts: &[u64; 4], count: usize) -> u64 {
+    counts.iter().enumerate().fold(!0, |acc, (bit, &plane)| {
+        acc & if count >> bit & 1 == 1 { plane } else { !plane }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        soup::{Region, Soup, Symmetry},
+        Board,
+    };
+
+    /// 同じスープを Board と BitBoard で進め、毎世代の盤面が一致することを確かめます。
+    fn assert_same_as_board(width: usize, height: usize, topology: Topology) {
+        let soup = Soup {
+            density: 0.4,
+            region: Region::Whole,
+            symmetry: Symmetry::C1,
+            seed: 42,
+        };
+        let mut board = Board::new(width, height, 1);
+        board.set_topology(topology);
+        board.set_live(soup.cells(width, height));
+        let mut bits = BitBoard::new(width, height, 1);
+        bits.set_topology(topology);
+        bits.set_live(soup.cells(width, height));
+        for generation in 0..40 {
+            let mut expected = board.to_pattern().cells;
+            let mut actual = bits.to_pattern().cells;
+            expected.sort();
+            actual.sort();
+            assert_eq!(
+                actual, expected,
+                "{}x{} {:?} の {} 世代目",
+                width, height, topology, generation
+            );
+            board.step();
+            bits.step();
+        }
+    }
+
+    #[test]
+    fn matches_board_on_bounded_boards() {
+        assert_same_as_board(64, 20, Topology::Bounded);
+        assert_same_as_board(128, 20, Topology::Bounded);
+        assert_same_as_board(70, 20, Topology::Bounded);
+        assert_same_as_board(13, 20, Topology::Bounded);
+    }
+
+    #[test]
+    fn matches_board_on_torus() {
+        assert_same_as_board(64, 20, Topology::Torus);
+        assert_same_as_board(128, 20, Topology::Torus);
+        assert_same_as_board(70, 20, Topology::Torus);
+        assert_same_as_board(13, 20, Topology::Torus);
+    }
+}
@@ -0,0 +1,345 @@
+//! 止まった盤面を、つながった物体ごとに分けて種類を数える国勢調査(センサス)です。
+
+use crate::{pattern::apgcode, rule::Rule, sparse::SparseBoard};
+use std::{collections::HashMap, fmt};
+
+/// 同じ物体とみなすセル同士の距離(チェビシェフ距離)の既定値
+/// 宇宙船のように1マス空いたセルを持つ物体もあるため、2 にしています。
+pub const DEFAULT_DISTANCE: i64 = 2;
+/// 物体の周期を調べるときに、最大で何世代進めるか
+const MAX_PERIOD: u64 = 120;
+
+/// 名前の分かっている物体です (B3/S23 のもの)。1つの位相を '/' で区切った行で書いています。
+const KNOWN_OBJECTS: [(&str, &str); 24] = [
+    ("block", "OO/OO"),
+    ("beehive", ".OO./O..O/.OO."),
+    ("loaf", ".OO./O..O/.O.O/..O."),
+    ("boat", "OO./O.O/.O."),
+    ("ship", "OO./O.O/.OO"),
+    ("tub", ".O./O.O/.O."),
+    ("pond", ".OO./O..O/O..O/.OO."),
+    ("long boat", "OO../O.O./.O.O/..O."),
+    ("barge", ".O../O.O./.O.O/..O."),
+    ("mango", ".OO../O..O./.O..O/..OO."),
+    ("long barge", ".O.../O.O../.O.O./..O.O/...O."),
+    ("eater 1", "OO../O.O./..O./..OO"),
+    ("snake", "OO.O/O.OO"),
+    ("aircraft carrier", "OO../O..O/..OO"),
+    ("blinker", "OOO"),
+    ("toad", ".OOO/OOO."),
+    ("beacon", "OO../OO../..OO/..OO"),
+    ("traffic light", "..OOO../......./O.....O/O.....O/O.....O/......./..OOO.."),
+    ("pulsar", "..OOO...OOO../............./O....O.O....O/O....O.O....O/O....O.O....O/..OOO...OOO../............./..OOO...OOO../O....O.O....O/O....O.O....O/O....O.O....O/............./..OOO...OOO.."),
+    ("pentadecathlon", "..O....O../OO.OOOO.OO/..O....O.."),
+    ("glider", ".O./..O/OOO"),
+    ("lightweight spaceship", ".O..O/O..../O...O/OOOO."),
+    ("middleweight spaceship", "...O../.O...O/O...../O....O/OOOOO."),
+    ("heavyweight spaceship", "...OO../.O....O/O....../O.....O/OOOOOO."),
+];
+
+/// 物体の種類です。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ObjectKind {
+    /// 変化しない
+    StillLife,
+    /// period 世代ごとに同じ位置に戻る
+    Oscillator { period: u64 },
+    /// period 世代ごとに (dx, dy) だけ移動した同じ形に戻る
+    Spaceship { period: u64, dx: i64, dy: i64 },
+    /// MAX_PERIOD 世代以内に同じ形に戻らない、または消える
+    Unknown,
+}
+impl fmt::Display for ObjectKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjectKind::StillLife => write!(f, "固定物"),
+            ObjectKind::Oscillator { period } => write!(f, "周期{}の振動子", period),
+            ObjectKind::Spaceship { period, .. } => write!(f, "周期{}の宇宙船", period),
+            ObjectKind::Unknown => write!(f, "不明な物体"),
+        }
+    }
+}
+
+/// センサスの1行分で、同じ形の物体をまとめたものです。
+#[derive(Debug, Clone, PartialEq)]
+pub struct CensusEntry {
+    /// 名前の分かっている物体なら、その名前
+    pub name: Option<&'static str>,
+    pub kind: ObjectKind,
+    /// 向きと位相をそろえた形(左上が (0, 0))
+    pub cells: Vec<(i64, i64)>,
+    /// 種類が分かった物体なら、その apgcode
+    pub apgcode: Option<String>,
+    pub count: usize,
+}
+impl CensusEntry {
+    /// 表示に使う名前です。名前の分からない物体は apgcode で、それもなければ種類とセル数で表します。
+    pub fn label(&self) -> String {
+        match (self.name, &self.apgcode) {
+            (Some(name), Some(code)) => format!("{} ({})", name, code),
+            (Some(name), None) => name.to_string(),
+            (None, Some(code)) => code.clone(),
+            (None, None) => format!("{} ({}セル)", self.kind, self.cells.len()),
+        }
+    }
+}
+
+/// 盤面にある物体を種類ごとに数えた結果です。
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Census {
+    /// 数の多い順に並べたもの
+    pub entries: Vec<CensusEntry>,
+}
+impl Census {
+    /// 生存セルの座標 cells を、距離 distance 以内でつながった物体に分けて数えます。
+    /// 物体はそれぞれ単独で rule に従って進め、種類と周期を調べます。
+    /// 物体の名前は、rule が B3/S23 のときだけ付けます。
+    pub fn take(cells: &[(i64, i64)], rule: &Rule, distance: i64) -> Census {
+        // 名前は B3/S23 での物体のものなので、ほかのルールでは付けずに apgcode で表す
+        let known: Vec<(&'static str, Vec<(i64, i64)>)> = if *rule == Rule::conway() {
+            KNOWN_OBJECTS
+                .iter()
+                .map(|&(name, rows)| (name, classify(&parse_rows(rows), rule).1))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let mut entries: Vec<CensusEntry> = Vec::new();
+        for object in components(cells, distance) {
+            let (kind, phases) = evolve(&object, rule);
+            let cells = phases
+                .iter()
+                .map(|p| canonical_orientation(p))
+                .min()
+                .unwrap_or_default();
+            match entries.iter_mut().find(|e| e.cells == cells) {
+                Some(entry) => entry.count += 1,
+                None => entries.push(CensusEntry {
+                    name: known
+                        .iter()
+                        .find(|(_, c)| *c == cells)
+                        .map(|&(name, _)| name),
+                    kind,
+                    cells,
+                    apgcode: apgcode::from_phases(kind, &phases),
+                    count: 1,
+                }),
+            }
+        }
+        entries.sort_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then(a.cells.len().cmp(&b.cells.len()))
+                .then(a.cells.cmp(&b.cells))
+        });
+        Census { entries }
+    }
+}
+impl fmt::Display for Census {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "内訳:")?;
+        if self.entries.is_empty() {
+            return write!(f, " なし");
+        }
+        for entry in &self.entries {
+            write!(f, "\n  {}: {}", entry.label(), entry.count)?;
+        }
+        Ok(())
+    }
+}
+
+/// '/' で区切った行から生存セルの座標を作ります。
+fn parse_rows(rows: &str) -> Vec<(i64, i64)> {
+    rows.split('/')
+        .enumerate()
+        .flat_map(|(y, row)| {
+            row.chars()
+                .enumerate()
+                .filter(|&(_, c)| c == 'O')
+                .map(move |(x, _)| (x as i64, y as i64))
+        })
+        .collect()
+}
+
+/// 距離 distance 以内にあるセル同士をつなぎ、つながったセルの集まりに分けます。
+fn components(cells: &[(i64, i64)], distance: i64) -> Vec<Vec<(i64, i64)>> {
+    let index: HashMap<(i64, i64), usize> =
+        cells.iter().enumerate().map(|(i, &c)| (c, i)).collect();
+    let mut parent: Vec<usize> = (0..cells.len()).collect();
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+    for (i, &(x, y)) in cells.iter().enumerate() {
+        for dy in -distance..=distance {
+            for dx in -distance..=distance {
+                if let Some(&j) = index.get(&(x + dx, y + dy)) {
+                    let (a, b) = (root(&mut parent, i), root(&mut parent, j));
+                    parent[a] = b;
+                }
+            }
+        }
+    }
+    let mut groups: HashMap<usize, Vec<(i64, i64)>> = HashMap::new();
+    for (i, &cell) in cells.iter().enumerate() {
+        groups.entry(root(&mut parent, i)).or_default().push(cell);
+    }
+    groups.into_values().collect()
+}
+
+/// 左上が (0, 0) になるように動かして並べ替え、(形, 動かす前の左上) を返します。
+fn normalize(cells: &[(i64, i64)]) -> (Vec<(i64, i64)>, (i64, i64)) {
+    let x0 = cells.iter().map(|&(x, _)| x).min().unwrap_or(0);
+    let y0 = cells.iter().map(|&(_, y)| y).min().unwrap_or(0);
+    let mut shape: Vec<(i64, i64)> = cells.iter().map(|&(x, y)| (x - x0, y - y0)).collect();
+    shape.sort();
+    (shape, (x0, y0))
+}
+
+/// 回転・鏡映の8通りの向きにした形を、それぞれ左上が (0, 0) になるようにして返します。
+pub fn orientations(cells: &[(i64, i64)]) -> Vec<Vec<(i64, i64)>> {
+    (0..8)
+        .map(|t| {
+            let transformed: Vec<(i64, i64)> = cells
+                .iter()
+                .map(|&(x, y)| {
+                    let (x, y) = if t & 4 != 0 { (y, x) } else { (x, y) };
+                    (
+                        if t & 1 != 0 { -x } else { x },
+                        if t & 2 != 0 { -y } else { y },
+                    )
+                })
+                .collect();
+            normalize(&transformed).0
+        })
+        .collect()
+}
+
+/// 回転・鏡映の8通りのうち、並べた座標が最も小さくなる向きの形を返します。
+fn canonical_orientation(cells: &[(i64, i64)]) -> Vec<(i64, i64)> {
+    orientations(cells).into_iter().min().unwrap_or_default()
+}
+
+/// 物体を単独で進めて種類を調べ、(種類, 1周期分の各位相の形) を返します。
+/// 形は左上が (0, 0) になるようにしたものです。種類が分からなければ、位相は最初の形だけになります。
+pub fn evolve(cells: &[(i64, i64)], rule: &Rule) -> (ObjectKind, Vec<Vec<(i64, i64)>>) {
+    let (start, (x0, y0)) = normalize(cells);
+    let mut board = SparseBoard::new(1);
+    board.set_rule(rule.clone());
+    board.set_live(cells.to_vec());
+    let mut phases = vec![start.clone()];
+    for period in 1..=MAX_PERIOD {
+        board.step();
+        let live = board.live_cells();
+        if live.is_empty() {
+            break;
+        }
+        let (shape, (x1, y1)) = normalize(&live);
+        if shape == start {
+            let (dx, dy) = (x1 - x0, y1 - y0);
+            let kind = match (period, dx, dy) {
+                (1, 0, 0) => ObjectKind::StillLife,
+                (_, 0, 0) => ObjectKind::Oscillator { period },
+                _ => ObjectKind::Spaceship { period, dx, dy },
+            };
+            return (kind, phases);
+        }
+        phases.push(shape);
+    }
+    (ObjectKind::Unknown, vec![start])
+}
+
+/// 物体を単独で進めて種類を調べ、(種類, 向きと位相をそろえた形) を返します。
+/// 形は、すべての位相と向きのうち最も小さくなるものです。
+fn classify(cells: &[(i64, i64)], rule: &Rule) -> (ObjectKind, Vec<(i64, i64)>) {
+    let (kind, phases) = evolve(cells, rule);
+    let cells = phases
+        .iter()
+        .map(|p| canonical_orientation(p))
+        .min()
+        .unwrap_or_default();
+    (kind, cells)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 左上を (x, y) にずらした '/' 区切りの行の生存セル
+    fn at(rows: &str, x: i64, y: i64) -> Vec<(i64, i64)> {
+        parse_rows(rows)
+            .into_iter()
+            .map(|(cx, cy)| (cx + x, cy + y))
+            .collect()
+    }
+
+    fn summary(census: &Census) -> Vec<(Option<&str>, ObjectKind, Option<&str>, usize)> {
+        census
+            .entries
+            .iter()
+            .map(|e| (e.name, e.kind, e.apgcode.as_deref(), e.count))
+            .collect()
+    }
+
+    #[test]
+    fn counts_block_blinker_and_glider() {
+        let mut cells = at("OO/OO", 0, 0);
+        cells.extend(at("OO/OO", 30, 30));
+        cells.extend(at("OOO", 10, 0));
+        cells.extend(at(".O./..O/OOO", 20, 20));
+        let census = Census::take(&cells, &Rule::conway(), DEFAULT_DISTANCE);
+        assert_eq!(
+            summary(&census),
+            [
+                (Some("block"), ObjectKind::StillLife, Some("xs4_33"), 2),
+                (
+                    Some("blinker"),
+                    ObjectKind::Oscillator { period: 2 },
+                    Some("xp2_7"),
+                    1
+                ),
+                (
+                    Some("glider"),
+                    ObjectKind::Spaceship {
+                        period: 4,
+                        dx: 1,
+                        dy: 1
+                    },
+                    Some("xq4_153"),
+                    1
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn distance_decides_which_cells_belong_together() {
+        // 1列空けて並べた2つのブロック
+        let mut cells = at("OO/OO", 0, 0);
+        cells.extend(at("OO/OO", 3, 0));
+        let apart = Census::take(&cells, &Rule::conway(), 1);
+        assert_eq!(
+            summary(&apart),
+            [(Some("block"), ObjectKind::StillLife, Some("xs4_33"), 2)]
+        );
+        let together = Census::take(&cells, &Rule::conway(), 2);
+        assert_eq!(together.entries.len(), 1);
+        assert_eq!(together.entries[0].name, None);
+        assert_eq!(together.entries[0].kind, ObjectKind::StillLife);
+        assert_eq!(together.entries[0].cells.len(), 8);
+    }
+
+    #[test]
+    fn names_only_conway_objects() {
+        let rule: Rule = "B36/S23".parse().unwrap();
+        let census = Census::take(&at("OO/OO", 0, 0), &rule, DEFAULT_DISTANCE);
+        assert_eq!(
+            summary(&census),
+            [(None, ObjectKind::StillLife, Some("xs4_33"), 1)]
+        );
+        assert_eq!(census.entries[0].label(), "xs4_33");
+    }
+}
@@ -0,0 +1,405 @@
+//! コマンドライン引数の解析を行います。
+
+use crate::{
+    census,
+    rule::{Rule, RuleError},
+    soup::{Region, Symmetry},
+    Topology,
+};
+use std::{fmt, path::PathBuf};
+
+/// --history に指定できる盤面の数の上限
+const MAX_HISTORY: usize = 1_000_000;
+
+pub const USAGE: &str = "\
+使い方: lifegame [オプション]
+
+オプション:
+  -W, --width N          盤面の幅 (省略時はパターンファイルの幅か 25)
+  -H, --height N         盤面の高さ (省略時はパターンファイルの高さか 25)
+      --history N        終了判定のために覚えておく盤面の数 (省略時は 100、最大 1000000)
+  -p, --pattern FILE     パターンファイル (.rle / .cells / .lif)
+      --apgcode CODE     apgcode で書いたパターン (例: xs4_33, xp2_7, xq4_153)
+  -r, --rule RULE        ルール (例: B3/S23, 23/3, B36/S23, Hensel 記法の B2n3/S23-q, tlife)
+                         Generations 系は 生存/誕生/状態数 で書く (例: /2/3, 345/2/4)
+                         3x3 の512通りの並びごとに決めるルールは MAP に続けて base64 で書く
+                         末尾の H で六角形の6近傍、V で上下左右の4近傍になる (例: B2/S34H, B2/S013V)
+                         Larger than Life は R半径,C状態数,M0|1,S最小..最大,B最小..最大,NM|NN|NC で書く
+  -g, --generations N    最大世代数
+  -o, --output MODE      表示方法: ascii / none / final / tui (省略時は ascii)
+                         tui は画面を描き直す対話モード (space: 一時停止, n: 1世代, +/-: 速さ, e: 編集, q: 終了)
+      --edit             対話モードを編集モードから始める (矢印: 移動, space: 切り替え, Enter: 実行)
+      --rewind N         巻き戻せるように覚えておく世代数 (対話モードでは省略時 1000)
+                         変化の多い大きな盤面では、差分が 128MiB に収まる世代数までになる
+                         対話モードでは ←/→ で1世代、</> で10世代ずつ前後に移動する
+  -d, --density P        生存確率 P (0.0〜1.0) でランダムに盤面を埋める
+  -s, --seed N           ランダムに埋めるときのシード (省略時は時刻から作り、実行結果に表示する)
+      --region AREA      ランダムに埋める範囲: whole / 幅x高さ (例: 16x16、盤面の中央に置く)
+      --symmetry TYPE    ランダムに埋めるときの対称性: C1 / D2 / D4 / D8 (省略時は C1)
+      --topology TYPE    盤面の端: bounded / torus (省略時は bounded)
+  -e, --engine TYPE      計算方法: dense / bitboard / sparse / hashlife (省略時は dense)
+  -t, --threads N        dense で世代を進めるスレッドの数 (auto でCPUの数、省略時は 1)
+      --save FILE        最後の盤面をパターンファイルに保存する
+      --census           最後の盤面の物体を種類ごとに数え、盤面全体の apgcode も表示する
+      --census-distance N
+                         同じ物体とみなすセルの距離 (省略時は 2)
+      --stats FILE       毎世代の生存セル数・誕生数・死亡数・範囲・ハッシュ値を CSV で書き出す
+      --summary FORMAT   実行結果のまとめ: text / json / none (省略時は text)
+  -h, --help             このヘルプを表示する";
+
+/// 盤面の表示方法です。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Output {
+    /// 毎世代表示する
+    Ascii,
+    /// 何も表示しない
+    None,
+    /// 最後の盤面だけを表示する
+    Final,
+    /// 端末の画面を描き直しながらキー操作で進める
+    Interactive,
+}
+
+/// 実行結果のまとめの出力形式です。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SummaryFormat {
+    Text,
+    Json,
+    None,
+}
+
+/// 世代を進める計算方法です。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Engine {
+    /// 固定サイズの Board
+    Dense,
+    /// 1セルを1ビットで持つ BitBoard
+    Bitboard,
+    /// 生存セルだけを持つ SparseBoard
+    Sparse,
+    /// HashLife (--generations が必要)
+    HashLife,
+}
+
+/// コマンドライン引数から作った設定です。
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub width: Option<usize>,
+    pub height: Option<usize>,
+    pub history: usize,
+    pub pattern: Option<PathBuf>,
+    pub apgcode: Option<String>,
+    pub rule: Option<Rule>,
+    pub max_generations: Option<u64>,
+    pub output: Output,
+    pub density: Option<f64>,
+    pub seed: Option<u64>,
+    pub region: Region,
+    pub symmetry: Symmetry,
+    pub topology: Topology,
+    pub engine: Engine,
+    pub save: Option<PathBuf>,
+    pub summary: SummaryFormat,
+    /// 世代ごとの統計を書き出す CSV ファイル
+    pub stats: Option<PathBuf>,
+    /// 最後の盤面の物体を数えるときの距離(--census が指定されなければ None)
+    pub census: Option<i64>,
+    /// 対話モードを編集モードから始める
+    pub edit: bool,
+    /// 巻き戻し用に覚えておく世代数
+    pub rewind: Option<usize>,
+    /// 世代を進めるスレッドの数
+    pub threads: usize,
+}
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            width: None,
+            height: None,
+            history: 100,
+            pattern: None,
+            apgcode: None,
+            rule: None,
+            max_generations: None,
+            output: Output::Ascii,
+            density: None,
+            seed: None,
+            region: Region::Whole,
+            symmetry: Symmetry::C1,
+            topology: Topology::Bounded,
+            engine: Engine::Dense,
+            save: None,
+            summary: SummaryFormat::Text,
+            stats: None,
+            census: None,
+            edit: false,
+            rewind: None,
+            threads: 1,
+        }
+    }
+}
+
+/// コマンドライン引数の解析に失敗した理由です。
+#[derive(Debug, Clone, PartialEq)]
+pub enum CliError {
+    /// --help が指定された
+    Help,
+    Unknown(String),
+    MissingValue(String),
+    InvalidValue {
+        flag: String,
+        value: String,
+    },
+    Rule(RuleError),
+    /// 一緒には使えないオプションの組み合わせ
+    Conflict(&'static str),
+}
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::Help => write!(f, "{}", USAGE),
+            CliError::Unknown(flag) => write!(f, "不明なオプションです: {}", flag),
+            CliError::MissingValue(flag) => write!(f, "{} には値が必要です", flag),
+            CliError::InvalidValue { flag, value } => {
+                write!(f, "{} の値が不正です: {}", flag, value)
+            }
+            CliError::Rule(e) => write!(f, "--rule: {}", e),
+            CliError::Conflict(message) => write!(f, "{}", message),
+        }
+    }
+}
+impl std::error::Error for CliError {}
+
+impl Config {
+    /// 引数(プログラム名を除く)を解析します。
+    /// 値は "--width 30" と "--width=30" のどちらの形でも指定できます。
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Config, CliError> {
+        let mut config = Config::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => {
+                    (flag.to_string(), Some(value.to_string()))
+                }
+                _ => (arg, None),
+            };
+            // 値を取らないオプション
+            match flag.as_str() {
+                "-h" | "--help" => return Err(CliError::Help),
+                "--census" => {
+                    config.census.get_or_insert(census::DEFAULT_DISTANCE);
+                    continue;
+                }
+                "--edit" => {
+                    config.edit = true;
+                    config.output = Output::Interactive;
+                    continue;
+                }
+                _ => {}
+            }
+            let value = match inline.or_else(|| args.next()) {
+                Some(value) => value,
+                None => return Err(CliError::MissingValue(flag)),
+            };
+            let invalid = || CliError::InvalidValue {
+                flag: flag.clone(),
+                value: value.clone(),
+            };
+            match flag.as_str() {
+                "-W" | "--width" => match value.parse() {
+                    Ok(n @ 1..) => config.width = Some(n),
+                    _ => return Err(invalid()),
+                },
+                "-H" | "--height" => match value.parse() {
+                    Ok(n @ 1..) => config.height = Some(n),
+                    _ => return Err(invalid()),
+                },
+                "--history" => match value.parse() {
+                    Ok(n @ 1..=MAX_HISTORY) => config.history = n,
+                    _ => return Err(invalid()),
+                },
+                "-p" | "--pattern" => config.pattern = Some(PathBuf::from(&value)),
+                "--apgcode" => config.apgcode = Some(value),
+                "-r" | "--rule" => config.rule = Some(value.parse().map_err(CliError::Rule)?),
+                "-g" | "--generations" => {
+                    config.max_generations = Some(value.parse().map_err(|_| invalid())?)
+                }
+                "-o" | "--output" => {
+                    config.output = match value.as_str() {
+                        "ascii" => Output::Ascii,
+                        "none" => Output::None,
+                        "final" | "final-only" => Output::Final,
+                        "tui" | "interactive" => Output::Interactive,
+                        _ => return Err(invalid()),
+                    }
+                }
+                "-d" | "--density" => match value.parse::<f64>() {
+                    Ok(p) if (0.0..=1.0).contains(&p) => config.density = Some(p),
+                    _ => return Err(invalid()),
+                },
+                "-s" | "--seed" => config.seed = Some(value.parse().map_err(|_| invalid())?),
+                "--region" => config.region = value.parse().map_err(|_| invalid())?,
+                "--symmetry" => config.symmetry = value.parse().map_err(|_| invalid())?,
+                "--topology" => {
+                    config.topology = match value.as_str() {
+                        "bounded" => Topology::Bounded,
+                        "torus" => Topology::Torus,
+                        _ => return Err(invalid()),
+                    }
+                }
+                "-e" | "--engine" => {
+                    config.engine = match value.as_str() {
+                        "dense" => Engine::Dense,
+                        "bitboard" => Engine::Bitboard,
+                        "sparse" => Engine::Sparse,
+                        "hashlife" => Engine::HashLife,
+                        _ => return Err(invalid()),
+                    }
+                }
+                "--rewind" => match value.parse() {
+                    Ok(n @ 1..) => config.rewind = Some(n),
+                    _ => return Err(invalid()),
+                },
+                "-t" | "--threads" => {
+                    config.threads = match value.as_str() {
+                        "auto" => std::thread::available_parallelism().map_or(1, |n| n.get()),
+                        value => match value.parse() {
+                            Ok(n @ 1..) => n,
+                            _ => return Err(invalid()),
+                        },
+                    }
+                }
+                "--census-distance" => match value.parse() {
+                    Ok(n @ 1..) => config.census = Some(n),
+                    _ => return Err(invalid()),
+                },
+                "--stats" => config.stats = Some(PathBuf::from(&value)),
+                "--save" => config.save = Some(PathBuf::from(&value)),
+                "--summary" => {
+                    config.summary = match value.as_str() {
+                        "text" => SummaryFormat::Text,
+                        "json" => SummaryFormat::Json,
+                        "none" => SummaryFormat::None,
+                        _ => return Err(invalid()),
+                    }
+                }
+                _ => return Err(CliError::Unknown(flag)),
+            }
+        }
+        if config.engine == Engine::HashLife && config.max_generations.is_none() {
+            return Err(CliError::MissingValue("--generations".to_string()));
+        }
+        if config.pattern.is_some() && config.apgcode.is_some() {
+            return Err(CliError::Conflict(
+                "--pattern と --apgcode は一緒に使えません",
+            ));
+        }
+        if config.stats.is_some()
+            && (config.engine == Engine::HashLife || config.output == Output::Interactive)
+        {
+            return Err(CliError::Conflict(
+                "--stats は --engine hashlife や対話モードでは使えません",
+            ));
+        }
+        // sparse と hashlife の盤面は無限に広がっていて端がない
+        if matches!(config.engine, Engine::Sparse | Engine::HashLife)
+            && (config.topology == Topology::Torus
+                || (config.density.is_none()
+                    && (config.width.is_some() || config.height.is_some())))
+        {
+            return Err(CliError::Conflict(
+                "--engine sparse と hashlife では --topology torus は使えず、--width/--height は --density で埋める範囲にしか使えません",
+            ));
+        }
+        if config.output == Output::Interactive && config.engine != Engine::Dense {
+            return Err(CliError::Conflict(
+                "--output tui は --engine dense でのみ使えます",
+            ));
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Config, CliError> {
+        Config::parse(args.split_whitespace().map(str::to_string))
+    }
+
+    fn is_conflict(result: Result<Config, CliError>) -> bool {
+        matches!(result, Err(CliError::Conflict(_)))
+    }
+
+    #[test]
+    fn accepts_both_value_forms() {
+        for args in [
+            "--width 30 --rule B36/S23 -g 10 --engine sparse --density 0.5",
+            "--width=30 --rule=B36/S23 -g 10 --engine=sparse --density=0.5",
+        ] {
+            let config = parse(args).unwrap();
+            assert_eq!(config.width, Some(30), "{}", args);
+            assert_eq!(config.rule, Some("B36/S23".parse().unwrap()), "{}", args);
+            assert_eq!(config.max_generations, Some(10), "{}", args);
+            assert_eq!(config.engine, Engine::Sparse, "{}", args);
+            assert_eq!(config.density, Some(0.5), "{}", args);
+        }
+        // '=' で値を付けられるのは長い名前だけ
+        assert_eq!(
+            parse("-W=30 40").unwrap_err(),
+            CliError::Unknown("-W=30".to_string())
+        );
+    }
+
+    #[test]
+    fn reports_bad_values() {
+        assert_eq!(
+            parse("--width 0").unwrap_err(),
+            CliError::InvalidValue {
+                flag: "--width".to_string(),
+                value: "0".to_string(),
+            }
+        );
+        for value in ["0", "10000000000000"] {
+            assert_eq!(
+                parse(&format!("--history {}", value)).unwrap_err(),
+                CliError::InvalidValue {
+                    flag: "--history".to_string(),
+                    value: value.to_string(),
+                }
+            );
+        }
+        assert_eq!(parse("--history 1000000").unwrap().history, 1_000_000);
+        assert_eq!(
+            parse("--width").unwrap_err(),
+            CliError::MissingValue("--width".to_string())
+        );
+        assert_eq!(
+            parse("--bogus 1").unwrap_err(),
+            CliError::Unknown("--bogus".to_string())
+        );
+        assert!(matches!(parse("--rule B9/S23"), Err(CliError::Rule(_))));
+        assert_eq!(parse("--help").unwrap_err(), CliError::Help);
+    }
+
+    #[test]
+    fn rejects_conflicting_options() {
+        assert_eq!(
+            parse("--engine hashlife").unwrap_err(),
+            CliError::MissingValue("--generations".to_string())
+        );
+        assert!(parse("--engine hashlife -g 100").is_ok());
+        assert!(is_conflict(parse("--pattern a.rle --apgcode xs4_33")));
+        assert!(is_conflict(parse("--engine sparse --topology torus")));
+        assert!(is_conflict(parse(
+            "--engine hashlife -g 10 --topology torus"
+        )));
+        assert!(is_conflict(parse("--engine sparse --width 30")));
+        assert!(parse("--engine sparse --width 30 --density 0.3").is_ok());
+        assert!(is_conflict(parse("--engine bitboard --output tui")));
+        assert!(is_conflict(parse("--engine sparse --edit")));
+        assert!(parse("--output tui").is_ok());
+        assert!(is_conflict(parse("--stats a.csv --output tui")));
+    }
+}
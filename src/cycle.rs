@@ -0,0 +1,289 @@
+use std::collections::VecDeque;
+use toolbox::{fnv1::FNV1, fnv1::FNV1_64};
+
+/// 盤面の状態を、そのまま比較できる形で保存したものです。
+/// Board ではセルの状態を1ビット(Generations 系のルールでは数ビット)ずつ詰めたもの、SparseBoard では生存セルの座標を並べたものになります。
+/// 移動する物体を見つけるために、生存セルの位置をずらしてそろえた形を入れることもあります。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot(pub Vec<u64>);
+impl Snapshot {
+    pub fn to_hash(&self) -> u64 {
+        let mut fnv1 = FNV1_64::new();
+        for word in &self.0 {
+            for byte in word.to_be_bytes() {
+                fnv1.hash(byte);
+            }
+        }
+        fnv1.finalize()
+    }
+}
+
+/// 盤面の繰り返しです。
+/// start 世代目の盤面が、period 世代ごとに (dx, dy) だけ移動して現れます。移動しなければ (0, 0) です。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cycle {
+    pub start: u64,
+    pub period: u64,
+    pub dx: i64,
+    pub dy: i64,
+}
+impl Cycle {
+    /// 端がつながった width x height の盤面で、移動量を盤面の半分以内の値に直します。
+    pub fn wrapped(self, width: i64, height: i64) -> Cycle {
+        let wrap = |d: i64, size: i64| {
+            let d = d.rem_euclid(size);
+            if d > size / 2 {
+                d - size
+            } else {
+                d
+            }
+        };
+        Cycle {
+            dx: wrap(self.dx, width),
+            dy: wrap(self.dy, height),
+            ..self
+        }
+    }
+}
+
+/// 過去の盤面を覚えておき、同じ盤面に戻ったことを検出します。
+/// ハッシュで候補を絞り込んだ後、保存しておいた盤面と完全に一致するかを確かめるため、
+/// ハッシュの衝突で誤って終了と判定することはありません。
+pub struct CycleDetector {
+    capacity: usize,
+    /// (ハッシュ, 世代, 盤面, 盤面をずらした量) を古い順に並べたもの
+    entries: VecDeque<(u64, u64, Snapshot, (i64, i64))>,
+}
+impl CycleDetector {
+    /// 直近 capacity 世代分の盤面を覚えておく検出器を作ります。
+    pub fn new(capacity: usize) -> Self {
+        CycleDetector {
+            capacity,
+            // capacity は大きく指定されることがあるので、前もって確保はしない
+            entries: VecDeque::new(),
+        }
+    }
+    /// generation 世代目の盤面を記録します。
+    /// offset は、盤面の位置をずらしてそろえた場合の元の位置で、ずらしていなければ (0, 0) です。
+    pub fn record(&mut self, generation: u64, snapshot: Snapshot, offset: (i64, i64)) {
+        self.record_hashed(snapshot.to_hash(), generation, snapshot, offset);
+    }
+    /// record と同じですが、計算済みの盤面のハッシュ値 hash を使います。
+    pub fn record_hashed(
+        &mut self,
+        hash: u64,
+        generation: u64,
+        snapshot: Snapshot,
+        offset: (i64, i64),
+    ) {
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((hash, generation, snapshot, offset));
+    }
+    /// generation 世代目以降に記録した盤面を捨てます。巻き戻したときに使います。
+    pub fn truncate(&mut self, generation: u64) {
+        self.entries.retain(|&(_, g, _, _)| g < generation);
+    }
+    /// 記録した盤面をすべて捨てます。
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+    /// generation 世代目の盤面が過去に現れていれば、その繰り返しを返します。
+    /// 移動量は、記録したときとの offset の差になります。
+    pub fn find(&self, generation: u64, snapshot: &Snapshot, offset: (i64, i64)) -> Option<Cycle> {
+        self.find_hashed(snapshot.to_hash(), generation, snapshot, offset, |_| true)
+    }
+    /// find と同じですが、計算済みの盤面のハッシュ値 hash を使い、accept が true を返す繰り返しだけを探します。
+    pub fn find_hashed(
+        &self,
+        hash: u64,
+        generation: u64,
+        snapshot: &Snapshot,
+        offset: (i64, i64),
+        accept: impl Fn(&Cycle) -> bool,
+    ) -> Option<Cycle> {
+        self.entries
+            .iter()
+            .rev()
+            .filter(|(h, _, s, _)| *h == hash && s == snapshot)
+            .map(|&(_, start, _, (x0, y0))| Cycle {
+                start,
+                period: generation - start,
+                dx: offset.0 - x0,
+                dy: offset.1 - y0,
+            })
+            .find(|cycle| accept(cycle))
+    }
+}
+
+/// セルの状態を bits ビットずつ u64 に詰めます。bits は 64 を割り切る値にします。
+pub fn pack(bits: usize, states: impl Iterator<Item = u8>) -> Vec<u64> {
+    let mut words = Vec::new();
+    for (i, state) in states.enumerate() {
+        let (word, shift) = (i * bits / 64, i * bits % 64);
+        if word == words.len() {
+            words.push(0);
+        }
+        words[word] |= (state as u64) << shift;
+    }
+    words
+}
+
+/// pack で詰めた words から、i 番目のセルの状態を取り出します。
+pub fn unpack(words: &[u64], bits: usize, i: usize) -> u8 {
+    let (word, shift) = (i * bits / 64, i * bits % 64);
+    (words[word] >> shift & ((1 << bits) - 1)) as u8
+}
+
+/// 端がつながった width x height の盤面で、生存セルを左上に寄せた形と、寄せる前の位置を返します。
+/// state は各セルの状態で、0 以外のセルを寄せる対象とし、形には bits ビットずつ詰めます。
+/// 縦横それぞれ、生存セルのない最も長い列(行)の並びの直後を左端(上端)とするため、
+/// 盤面の端をまたいで移動する物体も同じ形になります。
+/// 生存セルのない列(行)がなければ、その向きにはずらしません。
+pub fn torus_shape(
+    width: usize,
+    height: usize,
+    bits: usize,
+    state: impl Fn(usize, usize) -> u8,
+) -> (Snapshot, (i64, i64)) {
+    let mut columns = vec![false; width];
+    let mut rows = vec![false; height];
+    for (y, row) in rows.iter_mut().enumerate() {
+        for (x, column) in columns.iter_mut().enumerate() {
+            if state(x, y) != 0 {
+                *column = true;
+                *row = true;
+            }
+        }
+    }
+    let (x0, w) = cut(&columns);
+    let (y0, h) = cut(&rows);
+    let cells = (0..h).flat_map(|y| (0..w).map(move |x| ((x0 + x) % width, (y0 + y) % height)));
+    let mut words = vec![w as u64, h as u64];
+    words.extend(pack(bits, cells.map(|(x, y)| state(x, y))));
+    (Snapshot(words), (x0 as i64, y0 as i64))
+}
+
+/// 輪になった列の並び occupied で、空きの最も長い並びの直後の位置と、そこから使われている幅を返します。
+fn cut(occupied: &[bool]) -> (usize, usize) {
+    let n = occupied.len();
+    let first = match occupied.iter().position(|&o| o) {
+        Some(first) => first,
+        None => return (0, 0),
+    };
+    // first から一周しながら、空きの並びの長さを数える
+    let (mut best_start, mut best_gap, mut gap) = (0, 0, 0);
+    for i in 1..=n {
+        let j = (first + i) % n;
+        if occupied[j] {
+            if gap > best_gap {
+                best_start = j;
+                best_gap = gap;
+            }
+            gap = 0;
+        } else {
+            gap += 1;
+        }
+    }
+    (best_start, n - best_gap)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Board, Topology};
+
+    #[test]
+    fn hash_collision_is_not_a_cycle() {
+        // (2, 3) と (3, 2) のように、鏡に映した盤面のハッシュ値が同じになった場合
+        let (a, b) = (Snapshot(vec![2, 3]), Snapshot(vec![3, 2]));
+        let mut detector = CycleDetector::new(10);
+        detector.record_hashed(7, 0, a.clone(), (0, 0));
+        assert_eq!(detector.find_hashed(7, 1, &b, (0, 0), |_| true), None);
+        assert_eq!(
+            detector.find_hashed(7, 1, &a, (0, 0), |_| true),
+            Some(Cycle {
+                start: 0,
+                period: 1,
+                dx: 0,
+                dy: 0
+            })
+        );
+    }
+
+    #[test]
+    fn reports_start_and_period() {
+        let snapshot = |n: u64| Snapshot(vec![n]);
+        let mut detector = CycleDetector::new(10);
+        for (generation, n) in [1, 2, 3, 4].into_iter().enumerate() {
+            detector.record(generation as u64, snapshot(n), (0, 0));
+        }
+        // 4 世代目が 1 世代目と同じなら、1 世代目から周期 3
+        let cycle = detector.find(4, &snapshot(2), (5, -2)).unwrap();
+        assert_eq!(
+            (cycle.start, cycle.period, cycle.dx, cycle.dy),
+            (1, 3, 5, -2)
+        );
+        // 覚えている数を超えた古い盤面は見つからない
+        let mut detector = CycleDetector::new(2);
+        for generation in 0..3 {
+            detector.record(generation, snapshot(generation), (0, 0));
+        }
+        assert_eq!(detector.find(3, &snapshot(0), (0, 0)), None);
+        assert!(detector.find(3, &snapshot(1), (0, 0)).is_some());
+    }
+
+    #[test]
+    fn blinker_has_period_two() {
+        let mut board = Board::new(9, 9, 10);
+        board.set_live([(3, 4), (4, 4), (5, 4)]);
+        board.step();
+        assert_eq!(board.cycle(), None);
+        board.step();
+        assert_eq!(
+            board.cycle(),
+            Some(Cycle {
+                start: 0,
+                period: 2,
+                dx: 0,
+                dy: 0
+            })
+        );
+    }
+
+    #[test]
+    fn glider_moves_across_torus_edges() {
+        // 右下の角をまたいで置いたグライダーは、端を越えても (1, 1) 移動と判定される
+        let mut board = Board::new(8, 8, 10);
+        board.set_topology(Topology::Torus);
+        board.set_live([(7, 6), (0, 7), (6, 0), (7, 0), (0, 0)]);
+        for _ in 0..3 {
+            board.step();
+            assert_eq!(board.cycle(), None);
+        }
+        board.step();
+        assert_eq!(
+            board.cycle(),
+            Some(Cycle {
+                start: 0,
+                period: 4,
+                dx: 1,
+                dy: 1
+            })
+        );
+    }
+
+    #[test]
+    fn wraps_displacement_to_nearest() {
+        let cycle = |dx, dy| Cycle {
+            start: 0,
+            period: 4,
+            dx,
+            dy,
+        };
+        assert_eq!(cycle(7, 1).wrapped(8, 8), cycle(-1, 1));
+        assert_eq!(cycle(-9, 4).wrapped(8, 8), cycle(-1, 4));
+        assert_eq!(cycle(2, -6).wrapped(10, 6), cycle(2, 0));
+    }
+}
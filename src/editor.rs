@@ -0,0 +1,154 @@
+//! 対話モードの中で、カーソルを動かしながら盤面に初期配置を描く編集モードです。
+
+use crate::{
+    cli::Config,
+    random::Random,
+    tui::{self, Key, Terminal},
+    Board,
+};
+use std::{io, path::PathBuf, sync::mpsc::Receiver};
+
+/// r キーでランダムに埋める範囲の大きさ
+const RANDOM_REGION: usize = 16;
+/// --density が指定されていないときに、r キーで埋める生存確率
+const RANDOM_DENSITY: f64 = 0.5;
+/// --save が指定されていないときの保存先
+const DEFAULT_SAVE_PATH: &str = "pattern.rle";
+
+/// 編集モードを抜けたあとにすることです。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EditResult {
+    /// 世代を進め始める
+    Run,
+    /// 終了する
+    Quit,
+}
+
+/// 盤面のうち、画面に表示している範囲の左上の座標です。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Viewport {
+    pub x0: usize,
+    pub y0: usize,
+}
+impl Viewport {
+    /// (x, y) が rows x columns の表示範囲に入るように動かします。
+    fn follow(&mut self, x: usize, y: usize, rows: usize, columns: usize) {
+        if x < self.x0 {
+            self.x0 = x;
+        } else if x >= self.x0 + columns {
+            self.x0 = x + 1 - columns;
+        }
+        if y < self.y0 {
+            self.y0 = y;
+        } else if y >= self.y0 + rows {
+            self.y0 = y + 1 - rows;
+        }
+    }
+}
+
+/// 編集モードです。
+/// 矢印キーでカーソルを動かし、スペースでセルの生死を切り替えます。
+/// c で全消去、r でカーソルの周りをランダムに埋め、s でパターンファイルに保存し、
+/// Enter で世代を進め始め、q で終了します。
+pub fn edit(
+    board: &mut Board,
+    terminal: &Terminal,
+    keys: &Receiver<Key>,
+    viewport: &mut Viewport,
+    config: &Config,
+) -> io::Result<EditResult> {
+    let (mut x, mut y) = (board.width / 2, board.height / 2);
+    let mut random = Random::new(config.seed.unwrap_or_else(Random::seed_from_time));
+    let mut message = String::new();
+    loop {
+        let (rows, columns) = terminal.board_area();
+        viewport.follow(x, y, rows, tui::visible_cells(board, columns));
+        let mut lines = tui::render_board(board, viewport.x0, viewport.y0, rows, columns);
+        // カーソルの位置の文字を反転表示にする
+        if let Some(line) = lines.get_mut(y - viewport.y0) {
+            let i = tui::cell_position(board, y, x - viewport.x0);
+            line.replace_range(i..i + 1, &format!("\x1b[7m{}\x1b[0m", &line[i..i + 1]));
+        }
+        lines.push(format!(
+            "[編集] カーソル: ({}, {})  生存セル: {}  ルール: {}  {}",
+            x,
+            y,
+            board.population(),
+            board.rule,
+            message
+        ));
+        lines.push(
+            "矢印: 移動  space: 切り替え  c: 全消去  r: ランダム  s: 保存  Enter: 実行  q: 終了"
+                .to_string(),
+        );
+        terminal.draw(&lines)?;
+
+        message.clear();
+        match keys.recv().unwrap_or(Key::Char('q')) {
+            Key::Up => y = y.saturating_sub(1),
+            Key::Down => y = (y + 1).min(board.height - 1),
+            Key::Left => x = x.saturating_sub(1),
+            Key::Right => x = (x + 1).min(board.width - 1),
+            Key::Char(' ') => board.toggle(x, y),
+            Key::Char('c') => board.clear(),
+            Key::Char('r') => {
+                let half = RANDOM_REGION / 2;
+                board.fill_random(
+                    (
+                        x.saturating_sub(half),
+                        y.saturating_sub(half),
+                        RANDOM_REGION,
+                        RANDOM_REGION,
+                    ),
+                    config.density.unwrap_or(RANDOM_DENSITY),
+                    &mut random,
+                );
+            }
+            Key::Char('s') => {
+                let default = config
+                    .save
+                    .clone()
+                    .unwrap_or_else(|| PathBuf::from(DEFAULT_SAVE_PATH));
+                if let Some(path) = prompt(terminal, keys, &lines, &default)? {
+                    message = match board.to_pattern().save(&path) {
+                        Ok(()) => format!("保存しました: {}", path.display()),
+                        Err(e) => format!("保存できませんでした: {}", e),
+                    };
+                }
+            }
+            Key::Enter => {
+                board.forget_history();
+                return Ok(EditResult::Run);
+            }
+            Key::Char('q') => return Ok(EditResult::Quit),
+            _ => {}
+        }
+    }
+}
+
+/// 最後の行で保存先を入力させます。Esc で取り消すと None を返します。
+fn prompt(
+    terminal: &Terminal,
+    keys: &Receiver<Key>,
+    lines: &[String],
+    default: &std::path::Path,
+) -> io::Result<Option<PathBuf>> {
+    let mut input = default.display().to_string();
+    let mut lines = lines.to_vec();
+    lines.pop();
+    loop {
+        lines.push(format!("保存先 (Enter: 決定, Esc: 取り消し): {}", input));
+        terminal.draw(&lines)?;
+        lines.pop();
+        match keys.recv().unwrap_or(Key::Escape) {
+            Key::Enter if !input.is_empty() => return Ok(Some(PathBuf::from(input))),
+            Key::Escape => return Ok(None),
+            // Backspace / Delete
+            Key::Char('\x08' | '\x7f') => {
+                input.pop();
+            }
+            Key::Char(c) if !c.is_control() => input.push(c),
+            _ => {}
+        }
+    }
+}
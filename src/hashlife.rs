@@ -0,0 +1,405 @@
+use crate::{
+    rule::{self, Rule},
+    Board,
+};
+use std::collections::HashMap;
+use toolbox::{fnv1::FNV1, fnv1::FNV1_64};
+
+/// ノードの番号です。`HashLife::nodes` の添字になります。
+type NodeId = usize;
+
+/// 死んだセル(レベル0の葉)
+const DEAD: NodeId = 0;
+/// 生きているセル(レベル0の葉)
+const ALIVE: NodeId = 1;
+
+/// 4分木のノードです。
+/// レベル L のノードは 2^L x 2^L の正方形を表し、子は 北西・北東・南西・南東 の順に並びます。
+#[derive(Debug, Clone)]
+struct Node {
+    level: u32,
+    children: [NodeId; 4],
+    population: u64,
+}
+
+/// HashLife アルゴリズムで世代を進める、大きさに制限のない盤面です。
+/// 同じ内容のノードは1つにまとめ(正規化)、各ノードの未来の状態をメモ化することで、
+/// 規則的なパターンなら数百万世代先まで一気に進めることができます。
+/// B0 を含むルールとトーラスには対応していません。
+pub struct HashLife {
+    nodes: Vec<Node>,
+    /// 子ノードの FNV1_64 ハッシュから、そのハッシュを持つノードへの表
+    table: HashMap<u64, Vec<NodeId>>,
+    /// (ノード, k) から、そのノードの中央を 2^k 世代進めた結果への表
+    results: HashMap<(NodeId, u32), NodeId>,
+    /// empty[L] はレベル L の空のノード
+    empty: Vec<NodeId>,
+    root: NodeId,
+    /// ルートノードの左上の座標
+    origin: (i64, i64),
+    generation: u64,
+    rule: Rule,
+}
+impl HashLife {
+    pub fn new(rule: Rule) -> Self {
+        let leaf = |population| Node {
+            level: 0,
+            children: [DEAD; 4],
+            population,
+        };
+        let mut life = HashLife {
+            nodes: vec![leaf(0), leaf(1)],
+            table: HashMap::new(),
+            results: HashMap::new(),
+            empty: vec![DEAD],
+            root: DEAD,
+            origin: (-4, -4),
+            generation: 0,
+            rule,
+        };
+        life.root = life.empty(3);
+        life
+    }
+    /// 盤面の生存セルとルールを引き継いで HashLife を作ります。
+    pub fn from_board(board: &Board) -> Self {
+        let mut life = HashLife::new(board.rule.clone());
+        for y in 0..board.height {
+            for x in 0..board.width {
+                if board.array[y + 1][x + 1].is_live() {
+                    life.set_live(x as i64, y as i64);
+                }
+            }
+        }
+        life
+    }
+    /// width x height の盤面に書き戻します。盤面からはみ出したセルは捨てられます。
+    pub fn to_board(&self, width: usize, height: usize, board_histories: usize) -> Board {
+        let mut board = Board::new(width, height, board_histories);
+        board.set_rule(self.rule.clone());
+        board.set_live(
+            self.live_cells()
+                .into_iter()
+                .filter(|&(x, y)| (0..width as i64).contains(&x) && (0..height as i64).contains(&y))
+                .map(|(x, y)| (x as usize, y as usize)),
+        );
+        board
+    }
+    pub fn rule(&self) -> &Rule {
+        &self.rule
+    }
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+    pub fn population(&self) -> u64 {
+        self.nodes[self.root].population
+    }
+    /// (x, y) のセルを生きている状態にします。
+    pub fn set_live(&mut self, x: i64, y: i64) {
+        while !self.contains(x, y) {
+            self.expand();
+        }
+        self.root = self.set_node(self.root, x - self.origin.0, y - self.origin.1);
+    }
+    /// 生存セルの座標をすべて返します。
+    pub fn live_cells(&self) -> Vec<(i64, i64)> {
+        let mut cells = Vec::new();
+        self.collect_cells(self.root, self.origin.0, self.origin.1, &mut cells);
+        cells
+    }
+    /// 2^k 世代進めます。
+    pub fn step_pow2(&mut self, k: u32) {
+        // 2^k 世代の間にパターンがルートノードの外へ出ないよう、十分に余白を取る
+        while self.nodes[self.root].level < k + 2 || !self.is_padded() {
+            self.expand();
+        }
+        self.expand();
+        let quarter = 1i64 << (self.nodes[self.root].level - 2);
+        self.root = self.successor(self.root, k);
+        self.origin = (self.origin.0 + quarter, self.origin.1 + quarter);
+        self.generation += 1 << k;
+    }
+    /// n 世代進めます。n を2進数に分解して step_pow2 を繰り返します。
+    pub fn advance(&mut self, n: u64) {
+        for k in 0..u64::BITS {
+            if n >> k & 1 == 1 {
+                self.step_pow2(k);
+            }
+        }
+    }
+
+    /// 子ノードが同じノードがすでにあればそれを、なければ新しく作って返します。
+    fn join(&mut self, nw: NodeId, ne: NodeId, sw: NodeId, se: NodeId) -> NodeId {
+        let children = [nw, ne, sw, se];
+        let mut fnv1 = FNV1_64::new();
+        for id in children {
+            for byte in (id as u64).to_be_bytes() {
+                fnv1.hash(byte);
+            }
+        }
+        let hash = fnv1.finalize();
+        if let Some(bucket) = self.table.get(&hash) {
+            if let Some(&id) = bucket
+                .iter()
+                .find(|&&id| self.nodes[id].children == children)
+            {
+                return id;
+            }
+        }
+        let node = Node {
+            level: self.nodes[nw].level + 1,
+            children,
+            population: children.iter().map(|&id| self.nodes[id].population).sum(),
+        };
+        let id = self.nodes.len();
+        self.nodes.push(node);
+        self.table.entry(hash).or_default().push(id);
+        id
+    }
+    fn empty(&mut self, level: u32) -> NodeId {
+        while self.empty.len() <= level as usize {
+            let e = self.empty[self.empty.len() - 1];
+            let node = self.join(e, e, e, e);
+            self.empty.push(node);
+        }
+        self.empty[level as usize]
+    }
+    fn size(&self) -> i64 {
+        1 << self.nodes[self.root].level
+    }
+    fn contains(&self, x: i64, y: i64) -> bool {
+        let (x0, y0) = self.origin;
+        (x0..x0 + self.size()).contains(&x) && (y0..y0 + self.size()).contains(&y)
+    }
+    /// ルートノードを、現在のルートを中央に置いた1つ上のレベルのノードに置き換えます。
+    fn expand(&mut self) {
+        let level = self.nodes[self.root].level;
+        let [nw, ne, sw, se] = self.nodes[self.root].children;
+        let e = self.empty(level - 1);
+        let nw = self.join(e, e, e, nw);
+        let ne = self.join(e, e, ne, e);
+        let sw = self.join(e, sw, e, e);
+        let se = self.join(se, e, e, e);
+        self.root = self.join(nw, ne, sw, se);
+        let half = 1i64 << (level - 1);
+        self.origin = (self.origin.0 - half, self.origin.1 - half);
+    }
+    /// 生存セルがすべてルートノードの中央の半分に収まっているかを返します。
+    fn is_padded(&self) -> bool {
+        let [nw, ne, sw, se] = self.nodes[self.root].children;
+        let inner = [(nw, 3), (ne, 2), (sw, 1), (se, 0)];
+        inner.iter().all(|&(child, i)| {
+            let child = &self.nodes[child];
+            child.population == self.nodes[child.children[i]].population
+        })
+    }
+    fn set_node(&mut self, node: NodeId, x: i64, y: i64) -> NodeId {
+        let level = self.nodes[node].level;
+        if level == 0 {
+            return ALIVE;
+        }
+        let half = 1i64 << (level - 1);
+        let mut children = self.nodes[node].children;
+        let i = (if y >= half { 2 } else { 0 }) + (if x >= half { 1 } else { 0 });
+        children[i] = self.set_node(children[i], x % half, y % half);
+        self.join(children[0], children[1], children[2], children[3])
+    }
+    fn collect_cells(&self, node: NodeId, x: i64, y: i64, cells: &mut Vec<(i64, i64)>) {
+        let node = &self.nodes[node];
+        if node.population == 0 {
+            return;
+        }
+        if node.level == 0 {
+            cells.push((x, y));
+            return;
+        }
+        let half = 1i64 << (node.level - 1);
+        for (i, &child) in node.children.iter().enumerate() {
+            let (dx, dy) = ((i as i64 % 2) * half, (i as i64 / 2) * half);
+            self.collect_cells(child, x + dx, y + dy, cells);
+        }
+    }
+    /// ノードの中央の、1つ下のレベルのノードを返します。
+    fn centre(&mut self, node: NodeId) -> NodeId {
+        let [nw, ne, sw, se] = self.nodes[node].children;
+        self.join(
+            self.nodes[nw].children[3],
+            self.nodes[ne].children[2],
+            self.nodes[sw].children[1],
+            self.nodes[se].children[0],
+        )
+    }
+    /// レベル L のノードの中央 2^(L-1) x 2^(L-1) を 2^k 世代進めた結果を返します。(k <= L - 2)
+    fn successor(&mut self, node: NodeId, k: u32) -> NodeId {
+        let level = self.nodes[node].level;
+        debug_assert!(level >= 2 && k <= level - 2);
+        if self.nodes[node].population == 0 {
+            return self.empty(level - 1);
+        }
+        if let Some(&result) = self.results.get(&(node, k)) {
+            return result;
+        }
+        let result = if level == 2 {
+            self.base_case(node)
+        } else {
+            let [nw, ne, sw, se] = self.nodes[node].children;
+            let [_, nw_ne, nw_sw, nw_se] = self.nodes[nw].children;
+            let [ne_nw, _, ne_sw, ne_se] = self.nodes[ne].children;
+            let [sw_nw, sw_ne, _, sw_se] = self.nodes[sw].children;
+            let [se_nw, se_ne, se_sw, _] = self.nodes[se].children;
+            // 互いに半分ずつ重なる、1つ下のレベルの9つのノード
+            let parts = [
+                nw,
+                self.join(nw_ne, ne_nw, nw_se, ne_sw),
+                ne,
+                self.join(nw_sw, nw_se, sw_nw, sw_ne),
+                self.join(nw_se, ne_sw, sw_ne, se_nw),
+                self.join(ne_sw, ne_se, se_nw, se_ne),
+                sw,
+                self.join(sw_ne, se_nw, sw_se, se_sw),
+                se,
+            ];
+            // 全速で進める場合は2段階に分けてそれぞれ半分ずつ進め、
+            // そうでなければ1段目は中央を取り出すだけにする
+            let full_speed = k == level - 2;
+            let mut r = [DEAD; 9];
+            for (i, &part) in parts.iter().enumerate() {
+                r[i] = if full_speed {
+                    self.successor(part, k - 1)
+                } else {
+                    self.centre(part)
+                };
+            }
+            let k = if full_speed { k - 1 } else { k };
+            let nw = self.join(r[0], r[1], r[3], r[4]);
+            let ne = self.join(r[1], r[2], r[4], r[5]);
+            let sw = self.join(r[3], r[4], r[6], r[7]);
+            let se = self.join(r[4], r[5], r[7], r[8]);
+            let nw = self.successor(nw, k);
+            let ne = self.successor(ne, k);
+            let sw = self.successor(sw, k);
+            let se = self.successor(se, k);
+            self.join(nw, ne, sw, se)
+        };
+        self.results.insert((node, k), result);
+        result
+    }
+    /// 4x4 のノードの中央 2x2 を、ルールに従って1世代進めます。
+    fn base_case(&mut self, node: NodeId) -> NodeId {
+        let mut grid = [[false; 4]; 4];
+        for (y, row) in grid.iter_mut().enumerate() {
+            for (x, cell) in row.iter_mut().enumerate() {
+                let quadrant = self.nodes[node].children[(y / 2) * 2 + x / 2];
+                *cell = self.nodes[quadrant].children[(y % 2) * 2 + x % 2] == ALIVE;
+            }
+        }
+        let mut next = [DEAD; 4];
+        for (i, cell) in next.iter_mut().enumerate() {
+            let (x, y) = (1 + i % 2, 1 + i / 2);
+            let neighbourhood = rule::neighbourhood(|dx, dy| {
+                grid[(y as isize + dy) as usize][(x as isize + dx) as usize]
+            });
+            if self.rule.next_state(neighbourhood) {
+                *cell = ALIVE;
+            }
+        }
+        self.join(next[0], next[1], next[2], next[3])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        soup::{Region, Soup, Symmetry},
+        sparse::SparseBoard,
+    };
+
+    fn soup() -> Soup {
+        Soup {
+            density: 0.4,
+            region: Region::Centered {
+                width: 24,
+                height: 24,
+            },
+            symmetry: Symmetry::C1,
+            seed: 5,
+        }
+    }
+
+    fn sorted(mut cells: Vec<(i64, i64)>) -> Vec<(i64, i64)> {
+        cells.sort();
+        cells
+    }
+
+    #[test]
+    fn round_trips_board() {
+        let (width, height) = (40, 30);
+        let mut board = Board::new(width, height, 1);
+        board.set_rule("B36/S23".parse().unwrap());
+        board.set_live(soup().cells(width, height));
+        let life = HashLife::from_board(&board);
+        assert_eq!(life.rule(), &board.rule);
+        assert_eq!(life.population(), board.population());
+        let back = life.to_board(width, height, 1);
+        assert_eq!(back.rule, board.rule);
+        assert_eq!(back.snapshot(), board.snapshot());
+    }
+
+    /// 同じスープを HashLife で n 世代まとめて進めた結果と、SparseBoard で n 回進めた結果を比べます。
+    fn assert_same_as_sparse(rule: &str, n: u64) {
+        let cells: Vec<(i64, i64)> = soup()
+            .cells(40, 40)
+            .map(|(x, y)| (x as i64, y as i64))
+            .collect();
+        let mut life = HashLife::new(rule.parse().unwrap());
+        for &(x, y) in &cells {
+            life.set_live(x, y);
+        }
+        let mut sparse = SparseBoard::new(1);
+        sparse.set_rule(rule.parse().unwrap());
+        sparse.set_live(cells);
+        life.advance(n);
+        for _ in 0..n {
+            sparse.step();
+        }
+        assert_eq!(life.generation(), n);
+        assert_eq!(
+            life.population(),
+            sparse.population(),
+            "{} の {} 世代目",
+            rule,
+            n
+        );
+        assert_eq!(
+            sorted(life.live_cells()),
+            sorted(sparse.live_cells()),
+            "{} の {} 世代目",
+            rule,
+            n
+        );
+    }
+
+    #[test]
+    fn advance_matches_sparse_board() {
+        for rule in ["B3/S23", "B36/S23", "B2n3/S23-q"] {
+            for n in [1, 37, 100] {
+                assert_same_as_sparse(rule, n);
+            }
+        }
+    }
+
+    #[test]
+    fn step_pow2_adds_up() {
+        let mut once = HashLife::new(Rule::default());
+        let mut twice = HashLife::new(Rule::default());
+        for (x, y) in soup().cells(40, 40) {
+            once.set_live(x as i64, y as i64);
+            twice.set_live(x as i64, y as i64);
+        }
+        once.step_pow2(5);
+        twice.step_pow2(4);
+        twice.step_pow2(4);
+        assert_eq!(once.generation(), 32);
+        assert_eq!(sorted(once.live_cells()), sorted(twice.live_cells()));
+    }
+}
@@ -0,0 +1,154 @@
+use crate::cycle::Snapshot;
+use std::{collections::VecDeque, mem};
+
+/// 差分に使うバイト数の上限
+/// 盤面が大きく、毎世代多くのセルが変わるときは、覚えておける世代数がこれで決まります。
+pub const MAX_BYTES: usize = 128 << 20;
+
+/// 1つ後の世代の盤面との差分です。(変わった語の位置, 排他的論理和) を並べたものです。
+type Diff = Vec<(usize, u64)>;
+
+/// 巻き戻しのために、直近の世代の盤面を覚えておきます。
+/// 盤面を丸ごと持つのは最も新しい世代だけで、それより前の世代は1つ後の世代との差分で持ちます。
+/// 差分は排他的論理和なので、新しい世代から順に当てていけば過去の盤面に戻せます。
+pub struct History {
+    capacity: usize,
+    /// 最も新しく記録した (世代, 盤面)
+    latest: Option<(u64, Snapshot)>,
+    /// latest より前の各世代の差分を古い順に並べたもの。最後の要素が latest の1つ前の世代
+    diffs: VecDeque<Diff>,
+    /// diffs が使っているバイト数
+    bytes: usize,
+}
+impl History {
+    /// 直近 capacity 世代分の盤面を覚えておく履歴を作ります。
+    /// 差分が MAX_BYTES を超えると、capacity 世代に満たなくても古い世代から捨てます。
+    pub fn new(capacity: usize) -> Self {
+        History {
+            capacity,
+            latest: None,
+            diffs: VecDeque::new(),
+            bytes: 0,
+        }
+    }
+    /// generation 世代目の盤面を記録します。
+    /// 巻き戻した後に記録した場合は、それより後の世代の盤面を捨てます。
+    pub fn record(&mut self, generation: u64, snapshot: Snapshot) {
+        self.truncate(generation);
+        match self.latest.take() {
+            // 直前の世代があれば差分だけを覚え、なければ(世代が飛んだときも)覚え直す
+            Some((g, previous)) if g + 1 == generation && previous.0.len() == snapshot.0.len() => {
+                let diff: Diff = previous
+                    .0
+                    .iter()
+                    .zip(&snapshot.0)
+                    .enumerate()
+                    .filter(|(_, (a, b))| a != b)
+                    .map(|(i, (a, b))| (i, a ^ b))
+                    .collect();
+                self.bytes += diff_bytes(&diff);
+                self.diffs.push_back(diff);
+            }
+            _ => {
+                self.diffs.clear();
+                self.bytes = 0;
+            }
+        }
+        self.latest = Some((generation, snapshot));
+        while self.diffs.len() >= self.capacity || self.bytes > MAX_BYTES {
+            match self.diffs.pop_front() {
+                Some(diff) => self.bytes -= diff_bytes(&diff),
+                None => break,
+            }
+        }
+    }
+    /// generation 世代目以降の盤面を捨てます。
+    pub fn truncate(&mut self, generation: u64) {
+        while let Some((g, snapshot)) = &mut self.latest {
+            if *g < generation {
+                break;
+            }
+            match self.diffs.pop_back() {
+                Some(diff) => {
+                    self.bytes -= diff_bytes(&diff);
+                    apply(snapshot, &diff);
+                    *g -= 1;
+                }
+                None => self.latest = None,
+            }
+        }
+    }
+    /// generation 世代目の盤面を覚えていれば返します。
+    pub fn get(&self, generation: u64) -> Option<Snapshot> {
+        let (latest, snapshot) = self.latest.as_ref()?;
+        let steps = latest.checked_sub(generation)? as usize;
+        if steps > self.diffs.len() {
+            return None;
+        }
+        let mut snapshot = snapshot.clone();
+        for diff in self.diffs.iter().rev().take(steps) {
+            apply(&mut snapshot, diff);
+        }
+        Some(snapshot)
+    }
+    /// 覚えている (最も古い世代, 最も新しい世代) を返します。
+    pub fn range(&self) -> Option<(u64, u64)> {
+        let (latest, _) = self.latest.as_ref()?;
+        Some((latest - self.diffs.len() as u64, *latest))
+    }
+}
+
+/// 差分を盤面に当てます。同じ差分をもう一度当てると元に戻ります。
+fn apply(snapshot: &mut Snapshot, diff: &Diff) {
+    for &(i, bits) in diff {
+        snapshot.0[i] ^= bits;
+    }
+}
+
+/// 差分が使っているバイト数を返します。
+fn diff_bytes(diff: &Diff) -> usize {
+    diff.len() * mem::size_of::<(usize, u64)>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// generation 世代目の盤面として使う、世代ごとに違う値です。
+    fn snapshot(generation: u64) -> Snapshot {
+        Snapshot(
+            (0..4)
+                .map(|i| generation.wrapping_mul(0x9e37_79b9) >> i)
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn restores_recorded_generations() {
+        let mut history = History::new(5);
+        for generation in 0..8 {
+            history.record(generation, snapshot(generation));
+        }
+        assert_eq!(history.range(), Some((3, 7)));
+        for generation in 3..8 {
+            assert_eq!(history.get(generation), Some(snapshot(generation)));
+        }
+        assert_eq!(history.get(2), None);
+        assert_eq!(history.get(8), None);
+    }
+
+    #[test]
+    fn truncate_drops_later_generations() {
+        let mut history = History::new(10);
+        for generation in 0..6 {
+            history.record(generation, snapshot(generation));
+        }
+        history.truncate(4);
+        assert_eq!(history.range(), Some((0, 3)));
+        assert_eq!(history.get(3), Some(snapshot(3)));
+        // 巻き戻した後の記録は、捨てた世代の続きになる
+        history.record(4, snapshot(40));
+        assert_eq!(history.get(4), Some(snapshot(40)));
+        assert_eq!(history.get(0), Some(snapshot(0)));
+    }
+}
@@ -1,148 +1,920 @@
-use std::num::Wrapping;
-use toolbox::{fnv1::FNV1, fnv1::FNV1_64, ring_buffer::RingBuffer};
+mod bands;
+mod bitboard;
+mod census;
+mod cli;
+mod cycle;
+mod editor;
+mod hashlife;
+mod history;
+mod pattern;
+mod random;
+mod rule;
+mod run;
+mod soup;
+mod sparse;
+mod stats;
+mod summary;
+mod summed_area;
+mod tiles;
+mod tui;
+mod universe;
+
+use bands::{Bands, Job};
+use bitboard::BitBoard;
+use census::Census;
+use cli::{CliError, Config, Engine, Output, SummaryFormat};
+use cycle::{Cycle, CycleDetector, Snapshot};
+use hashlife::HashLife;
+use history::History;
+use pattern::Pattern;
+use random::Random;
+use rule::{LargerThanLife, Neighbourhood, Rule, Shape};
+use soup::Soup;
+use sparse::SparseBoard;
+use stats::StatsLog;
+use std::{fs::File, io::BufWriter, sync::Arc};
+use summary::{RunOutcome, RunSummary};
+use summed_area::SummedArea;
+use tiles::{TileStats, Tiles, TILE_SIZE};
+/// 盤面の端の扱いです。
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Topology {
+    /// 盤面の外側はすべて死んだセルとして扱う
+    Bounded,
+    /// 上下・左右の端がつながっている(トーラス)
+    Torus,
+}
 /// 盤面を表す実装です。
 /// 盤面全体の状態を管理します。
 struct Board {
     array: Vec<Vec<Cell>>,
-    old_hash: RingBuffer<Option<u64>>,
+    old_boards: CycleDetector,
+    /// 巻き戻し用の履歴(enable_rewind を呼ぶまでは None)
+    history: Option<History>,
+    generation: u64,
     width: usize,
     height: usize,
+    rule: Rule,
+    topology: Topology,
+    /// 世代を帯に分けて並列に進めるときのスレッド(1スレッドなら None)
+    bands: Option<Bands>,
+    /// 前の世代で変化したタイル
+    tiles: Tiles,
+    tile_stats: TileStats,
+    /// 直前の1世代で (生まれたセル, 死んだセル) の数
+    changes: (u64, u64),
+    /// 生存セルの数。世代を進めるときは誕生数と死亡数から求める
+    population: u64,
+    /// 今の盤面の (繰り返しの判定に使う形, その位置, 形のハッシュ値)
+    /// 世代を進めた直後に一度だけ計算し、cycle と次の世代の記録で使い回す
+    shape: Option<(Snapshot, (i64, i64), u64)>,
 }
 impl Board {
     fn new(x: usize, y: usize, board_histories: usize) -> Self {
         Board {
             array: vec![vec![Cell::new(); x + 2]; y + 2],
-            old_hash: RingBuffer::new(board_histories, None),
+            old_boards: CycleDetector::new(board_histories),
+            history: None,
+            generation: 0,
             width: x,
             height: y,
+            rule: Rule::default(),
+            topology: Topology::Bounded,
+            bands: None,
+            tiles: Tiles::new(x, y),
+            tile_stats: TileStats::default(),
+            changes: (0, 0),
+            population: 0,
+            shape: None,
         }
     }
-    fn get_board_size(&self) -> (usize, usize) {
-        (self.width, self.height)
+    /// 世代交代に使うルールを設定します。
+    fn set_rule(&mut self, rule: Rule) {
+        self.rule = rule;
+        self.tiles.mark_all();
+        self.touched();
+    }
+    /// 盤面の端の扱いを設定します。
+    fn set_topology(&mut self, topology: Topology) {
+        self.topology = topology;
+        self.tiles.mark_all();
+        self.touched();
+    }
+    /// 世代を進めるときに使うスレッドの数を設定します。2以上なら盤面を横長の帯に分けて並列に計算します。
+    fn set_threads(&mut self, threads: usize) {
+        self.bands = (threads > 1).then(|| Bands::new(threads));
     }
-    fn set_live(&mut self, points: Vec<(usize, usize)>) {
+    fn set_live(&mut self, points: impl IntoIterator<Item = (usize, usize)>) {
         for (x, y) in points {
             self.array[y + 1][x + 1].set_state(CellState::Live);
+            self.tiles.mark(x, y);
         }
+        self.touched();
     }
-    fn reflesh_state(&mut self) {
-        for y in 1..self.array.len() - 1 {
-            for x in 1..self.array[y].len() - 1 {
-                // セルが生きてたら、周りのセルに対して生存セルが1つ有ることを通知する
-                if self.array[y][x].is_live() {
-                    for y0 in 0..=2 {
-                        for x0 in 0..=2 {
-                            self.array[y + y0 - 1][x + x0 - 1].touch();
-                        }
-                    }
-                    // 自分自身に対しての通知操作は取り消す。
-                    self.array[y][x].untouch();
+    /// 消えかけのセルを (x, y, 状態番号) で置きます。ルールの状態数に収まらない状態は無視します。
+    fn set_dying(&mut self, points: impl IntoIterator<Item = (usize, usize, u8)>) {
+        for (x, y, state) in points {
+            if (state as usize) < self.rule.states() {
+                self.array[y + 1][x + 1].set_state(CellState::from_index(state));
+                self.tiles.mark(x, y);
+            }
+        }
+        self.touched();
+    }
+    /// (x0, y0) から width x height の範囲の各セルを、確率 density で生きている状態にします。
+    /// 盤面からはみ出した部分は無視します。
+    fn fill_random(
+        &mut self,
+        (x0, y0, width, height): (usize, usize, usize, usize),
+        density: f64,
+        random: &mut Random,
+    ) {
+        for y in y0..self.height.min(y0 + height) {
+            for x in x0..self.width.min(x0 + width) {
+                if random.chance(density) {
+                    self.array[y + 1][x + 1].set_state(CellState::Live);
+                    self.tiles.mark(x, y);
                 }
             }
         }
+        self.touched();
     }
-    fn commit_state(&mut self) {
-        // コミット前の盤面のハッシュを取得しておく
-        // もし、この状態と、is_doneメソッドが呼ばれた時に算出したハッシュが同一であれば終了と判定する。
-        self.old_hash.enqueue(Some(self.to_hash()));
+    /// (x, y) のセルの生死を反転します。
+    fn toggle(&mut self, x: usize, y: usize) {
+        let cell = &mut self.array[y + 1][x + 1];
+        let state = if cell.is_live() {
+            CellState::Dead
+        } else {
+            CellState::Live
+        };
+        cell.set_state(state);
+        self.tiles.mark(x, y);
+        self.touched();
+    }
+    /// すべてのセルを死んだ状態にします。
+    fn clear(&mut self) {
         self.array
             .iter_mut()
-            .for_each(|row| row.into_iter().for_each(|cell| cell.commit_state()));
-    }
-    fn to_hash(&self) -> u64 {
-        let mut fnv1 = FNV1_64::new();
-        for y in 1..self.array.len() - 1 {
-            for x in 1..self.array[y].len() - 1 {
-                let cell_data = ((Wrapping(x) * Wrapping(y)).0 as u64)
-                    * if self.array[y][x].is_live() { 1 } else { 0 };
-                for byte in cell_data.to_be_bytes() {
-                    fnv1.hash(byte);
+            .for_each(|row| row.iter_mut().for_each(|cell| cell.clear()));
+        self.tiles.mark_all();
+        self.touched();
+    }
+    /// 盤面を世代交代以外で書き換えた後に、生存セルの数を数え直し、覚えておいた形を捨てます。
+    fn touched(&mut self) {
+        self.population = self
+            .array
+            .iter()
+            .map(|row| row.iter().filter(|cell| cell.is_live()).count() as u64)
+            .sum();
+        self.shape = None;
+    }
+    /// 盤面を手で書き換えたときに、それまでの履歴を捨てます。
+    /// 書き換え前の盤面と一致しても、繰り返しとは判定しなくなります。
+    fn forget_history(&mut self) {
+        self.old_boards.clear();
+        if let Some(history) = &mut self.history {
+            history.truncate(self.generation);
+        }
+    }
+    /// 直近 capacity 世代分の盤面を覚えておき、巻き戻せるようにします。
+    fn enable_rewind(&mut self, capacity: usize) {
+        self.history = Some(History::new(capacity));
+    }
+    /// 巻き戻せる (最も古い世代, 最も新しい世代) を返します。
+    fn rewind_range(&self) -> Option<(u64, u64)> {
+        let (oldest, newest) = self.history.as_ref()?.range()?;
+        Some((oldest, newest.max(self.generation)))
+    }
+    /// 1世代前に戻ります。履歴に残っていなければ false を返します。
+    fn step_back(&mut self) -> bool {
+        self.generation > 0 && self.jump_to(self.generation - 1)
+    }
+    /// generation 世代目の盤面にします。
+    /// 過去の世代は履歴から戻し(残っていなければ false を返す)、未来の世代は計算して進めます。
+    fn jump_to(&mut self, generation: u64) -> bool {
+        if generation >= self.generation {
+            while self.generation < generation {
+                self.step();
+            }
+            return true;
+        }
+        let current = self.snapshot();
+        let history = match &mut self.history {
+            Some(history) => history,
+            None => return false,
+        };
+        let snapshot = match history.get(generation) {
+            Some(snapshot) => snapshot,
+            None => return false,
+        };
+        // 戻った後も今の世代まで早送りできるように、今の盤面も覚えておく
+        if history
+            .range()
+            .is_some_and(|(_, newest)| newest < self.generation)
+        {
+            history.record(self.generation, current);
+        }
+        self.restore(&snapshot);
+        self.generation = generation;
+        // 巻き戻した世代より後の盤面とは、繰り返しを判定しない
+        self.old_boards.truncate(generation);
+        true
+    }
+    /// snapshot で作った盤面の状態に戻します。
+    fn restore(&mut self, snapshot: &Snapshot) {
+        let bits = self.rule.state_bits();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let state = cycle::unpack(&snapshot.0, bits, y * self.width + x);
+                self.array[y + 1][x + 1].set_state(CellState::from_index(state));
+            }
+        }
+        self.tiles.mark_all();
+        self.touched();
+    }
+    /// 計算が必要なタイル(active)のセルについて、周りを含めた 3x3 の範囲の生存セルの並びを調べます。
+    fn reflesh_state(&mut self, active: &[bool]) {
+        if let Some(rule) = self.rule.larger_than_life().cloned() {
+            self.reflesh_range(active, &rule);
+            return;
+        }
+        let (width, height) = (self.width, self.height);
+        for tile in (0..active.len()).filter(|&i| active[i]) {
+            let (xs, ys) = self.tiles.cells(tile);
+            for y in ys.map(|y| y + 1) {
+                for x in xs.clone().map(|x| x + 1) {
+                    let neighbourhood = rule::neighbourhood(|dx, dy| {
+                        let (x0, y0) = ((dx + 1) as usize, (dy + 1) as usize);
+                        let (ny, nx) = match self.topology {
+                            // 盤面の外は外周の番兵セル(常に死んでいる)になる
+                            Topology::Bounded => (y + y0 - 1, x + x0 - 1),
+                            // 外周の番兵セルではなく反対側の端のセルを見る
+                            Topology::Torus => (
+                                (y + y0 + height - 2) % height + 1,
+                                (x + x0 + width - 2) % width + 1,
+                            ),
+                        };
+                        self.array[ny][nx].is_live()
+                    });
+                    self.array[y][x].neighbourhood = neighbourhood & self.rule.mask(y - 1);
+                }
+            }
+        }
+    }
+    /// Larger than Life のルールで、計算が必要なタイル(active)のセルについて、
+    /// 中央のセルを含めた範囲内の生存セルの数を数えます。
+    /// 範囲の各行の数は累積和の表から定数時間で求めるため、半径が大きくても行の数に比例する時間で済みます。
+    fn reflesh_range(&mut self, active: &[bool], rule: &LargerThanLife) {
+        let sums = SummedArea::new(
+            self.width,
+            self.height,
+            rule.range,
+            self.topology == Topology::Torus,
+            |x, y| self.array[y + 1][x + 1].is_live(),
+        );
+        let r = rule.range as isize;
+        let widths: Vec<isize> = (0..=rule.range)
+            .map(|dy| rule.row_width(dy) as isize)
+            .collect();
+        for tile in (0..active.len()).filter(|&i| active[i]) {
+            let (xs, ys) = self.tiles.cells(tile);
+            for y in ys {
+                for x in xs.clone() {
+                    let (cx, cy) = (x as isize, y as isize);
+                    let count = match rule.shape {
+                        Shape::Moore => sums.sum(cx - r, cy - r, cx + r + 1, cy + r + 1),
+                        _ => (-r..=r)
+                            .map(|dy| {
+                                let w = widths[dy.unsigned_abs()];
+                                sums.sum(cx - w, cy + dy, cx + w + 1, cy + dy + 1)
+                            })
+                            .sum(),
+                    };
+                    self.array[y + 1][x + 1].neighbourhood = count as u16;
                 }
             }
         }
-        fnv1.finalize()
+    }
+    /// コミット前の盤面を記録し、世代を1つ進めます。
+    fn record_generation(&mut self) {
+        // もし、この状態と、cycleメソッドが呼ばれた時の盤面が同一であれば終了と判定する。
+        let (shape, offset, hash) = self.shape.take().unwrap_or_else(|| self.hashed_shape());
+        if self.history.is_some() {
+            // 巻き戻しには、寄せていない盤面をそのまま使う
+            let snapshot = match self.topology {
+                Topology::Bounded => shape.clone(),
+                Topology::Torus => self.snapshot(),
+            };
+            if let Some(history) = &mut self.history {
+                history.record(self.generation, snapshot);
+            }
+        }
+        self.old_boards
+            .record_hashed(hash, self.generation, shape, offset);
+        self.generation += 1;
+    }
+    /// 計算が必要なタイル(active)のセルを次の世代の状態にし、変化したタイルを覚えておきます。
+    fn commit_state(&mut self, active: &[bool]) {
+        self.record_generation();
+        self.changes = (0, 0);
+        for tile in (0..active.len()).filter(|&i| active[i]) {
+            let (xs, ys) = self.tiles.cells(tile);
+            for y in ys {
+                for x in xs.clone() {
+                    let cell = &mut self.array[y + 1][x + 1];
+                    let (was, was_live) = (cell.now_state, cell.is_live());
+                    cell.commit_state(&self.rule);
+                    // 消えかけのセルは周りの数を変えないが、次の世代も状態を進める必要がある
+                    if cell.now_state != was {
+                        self.tiles.mark(x, y);
+                    }
+                    match (was_live, cell.is_live()) {
+                        (false, true) => self.changes.0 += 1,
+                        (true, false) => self.changes.1 += 1,
+                        _ => {}
+                    }
+                }
+            }
+        }
+        let computed = active.iter().filter(|&&a| a).count() as u64;
+        self.tile_stats.computed += computed;
+        self.tile_stats.skipped += active.len() as u64 - computed;
+    }
+    /// 1世代進めます。
+    fn step(&mut self) {
+        // 帯ののりしろは1行なので、3x3 より広い範囲を数えるルールは1スレッドで計算する
+        if self.bands.is_some() && self.rule.larger_than_life().is_none() {
+            self.step_parallel();
+        } else {
+            let active = self.tiles.take_active(self.topology == Topology::Torus);
+            self.reflesh_state(&active);
+            self.commit_state(&active);
+        }
+        let (births, deaths) = self.changes;
+        self.population = self.population + births - deaths;
+        self.shape = Some(self.hashed_shape());
+    }
+    /// 盤面を横長の帯に分け、帯ごとにスレッドで1世代進めます。結果は step と同じです。
+    /// 各帯は、すぐ上と下の行(のりしろ)の生死を先に写し取っておくことで、
+    /// 他の帯の書き換えを待たずに自分の行だけを数えて更新します。
+    /// 計算するのは step と同じく変化したタイルの周りだけで、それがない帯はスレッドに渡しません。
+    fn step_parallel(&mut self) {
+        let active = Arc::new(self.tiles.take_active(self.topology == Topology::Torus));
+        self.record_generation();
+        let (height, topology) = (self.height, self.topology);
+        let bands = match &self.bands {
+            Some(bands) => bands,
+            None => return,
+        };
+        let band_height = height.div_ceil(bands.threads());
+        let tile_columns = self.tiles.columns();
+        let live_row = |row: &[Cell]| row.iter().map(Cell::is_live).collect::<Vec<bool>>();
+        // 先にすべての帯ののりしろを写し取ってから、帯の行をスレッドに渡す
+        let halos: Vec<(usize, Vec<bool>, Vec<bool>)> = (0..height)
+            .step_by(band_height)
+            .filter(|&top| {
+                let bottom = (top + band_height).min(height) - 1;
+                (top / TILE_SIZE..=bottom / TILE_SIZE).any(|ty| {
+                    active[ty * tile_columns..(ty + 1) * tile_columns]
+                        .iter()
+                        .any(|&a| a)
+                })
+            })
+            .map(|top| {
+                let bottom = (top + band_height).min(height) - 1;
+                // 配列の行番号は番兵の行の分だけずれる
+                let (above, below) = match topology {
+                    Topology::Bounded => (top, bottom + 2),
+                    Topology::Torus => ((top + height - 1) % height + 1, (bottom + 1) % height + 1),
+                };
+                (
+                    top,
+                    live_row(&self.array[above]),
+                    live_row(&self.array[below]),
+                )
+            })
+            .collect();
+        let jobs = halos
+            .into_iter()
+            .map(|(top, above, below)| {
+                let bottom = (top + band_height).min(height);
+                Job {
+                    top,
+                    rows: self.array[top + 1..=bottom]
+                        .iter_mut()
+                        .map(std::mem::take)
+                        .collect(),
+                    above,
+                    below,
+                    rule: self.rule.clone(),
+                    topology,
+                    active: Arc::clone(&active),
+                    tile_columns,
+                }
+            })
+            .collect();
+        self.changes = (0, 0);
+        for done in bands.run(jobs) {
+            for (i, row) in done.rows.into_iter().enumerate() {
+                self.array[done.top + 1 + i] = row;
+            }
+            for tile in done.dirty {
+                self.tiles.mark_tile(tile);
+            }
+            self.changes.0 += done.changes.0;
+            self.changes.1 += done.changes.1;
+        }
+        let computed = active.iter().filter(|&&a| a).count() as u64;
+        self.tile_stats.computed += computed;
+        self.tile_stats.skipped += active.len() as u64 - computed;
+    }
+    /// 直前の1世代で (生まれたセル, 死んだセル) の数を返します。
+    fn births_and_deaths(&self) -> (u64, u64) {
+        self.changes
+    }
+    /// 盤面の状態のハッシュ値を返します。
+    fn state_hash(&self) -> u64 {
+        match (&self.shape, self.topology) {
+            // 端のある盤面では、繰り返しの判定に使う形が盤面の状態そのもの
+            (Some((_, _, hash)), Topology::Bounded) => *hash,
+            _ => self.snapshot().to_hash(),
+        }
+    }
+    /// これまでに再計算したタイルと省略したタイルの数を返します。
+    fn tile_stats(&self) -> TileStats {
+        self.tile_stats
+    }
+    /// セルの状態を1ビット(Generations 系のルールでは数ビット)ずつ詰めた盤面の状態を返します。
+    fn snapshot(&self) -> Snapshot {
+        Snapshot(cycle::pack(
+            self.rule.state_bits(),
+            (0..self.height).flat_map(|y| {
+                (0..self.width).map(move |x| self.array[y + 1][x + 1].now_state.index())
+            }),
+        ))
+    }
+    /// 繰り返しの判定に使う盤面の状態と、その位置を返します。
+    /// 端がつながっている盤面では、移動する物体も見つけられるように生存セルを左上に寄せます。
+    /// 端のある盤面では、物体は端に当たると形が変わるため、盤面をそのまま比べます。
+    fn shape(&self) -> (Snapshot, (i64, i64)) {
+        match self.topology {
+            Topology::Bounded => (self.snapshot(), (0, 0)),
+            Topology::Torus => {
+                cycle::torus_shape(self.width, self.height, self.rule.state_bits(), |x, y| {
+                    self.array[y + 1][x + 1].now_state.index()
+                })
+            }
+        }
+    }
+    /// shape に、形のハッシュ値を添えて返します。
+    fn hashed_shape(&self) -> (Snapshot, (i64, i64), u64) {
+        let (shape, offset) = self.shape();
+        let hash = shape.to_hash();
+        (shape, offset, hash)
     }
     fn show_board(&self) {
-        for row in &self.array {
+        let hexagonal = self.rule.neighbourhood() == Neighbourhood::Hexagonal;
+        for (y, row) in self.array.iter().enumerate() {
             print!("[");
-            for cell in row {
-                print!("{}", if cell.is_live() { "*" } else { " " });
+            // 六角形の近傍では、セルの間に空白を入れ、奇数行(番兵の行を含めて数えると偶数番目)を半セル右にずらす
+            if hexagonal && y % 2 == 0 {
+                print!(" ");
+            }
+            for (x, cell) in row.iter().enumerate() {
+                if hexagonal && x > 0 {
+                    print!(" ");
+                }
+                print!("{}", cell.now_state.glyph(self.rule.states(), ' '));
+            }
+            if hexagonal && y % 2 == 1 {
+                print!(" ");
             }
             println!("]");
         }
         println!("======================================");
     }
-    fn is_done(&self) -> bool {
-        self.old_hash.contains(Some(self.to_hash()))
+    fn generation(&self) -> u64 {
+        self.generation
+    }
+    /// 生存セルの数を返します。
+    fn population(&self) -> u64 {
+        self.population
+    }
+    /// 生存セルを囲む最小の矩形 (min_x, min_y, max_x, max_y) を返します。
+    fn bounding_box(&self) -> Option<(i64, i64, i64, i64)> {
+        summary::bounding_box((0..self.height).flat_map(|y| {
+            (0..self.width)
+                .filter(move |&x| self.array[y + 1][x + 1].is_live())
+                .map(move |x| (x as i64, y as i64))
+        }))
+    }
+    /// 現在の盤面が過去に現れていれば、その繰り返しを返します。
+    fn cycle(&self) -> Option<Cycle> {
+        let computed;
+        let (shape, offset, hash) = match &self.shape {
+            Some(shape) => shape,
+            None => {
+                computed = self.hashed_shape();
+                &computed
+            }
+        };
+        // 六角形の近傍は行の偶奇で数えるセルが違うので、奇数行ずれて同じ形になっても同じ動きはしない
+        let hexagonal = self.rule.neighbourhood() == Neighbourhood::Hexagonal;
+        self.old_boards
+            .find_hashed(*hash, self.generation, shape, *offset, |cycle| {
+                !hexagonal || cycle.dy % 2 == 0
+            })
+            .map(|cycle| cycle.wrapped(self.width as i64, self.height as i64))
     }
 }
 
+/// 消えかけのセルの表示に使う文字です。死んだ直後のものほど前の文字になります。
+const DYING_GLYPHS: [char; 4] = ['o', '+', '=', '-'];
+
 /// Cellの状態を管理します。
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 enum CellState {
     Dead,
     Live,
+    /// Generations 系のルールで、生存セルが死ぬまでに通る消えかけの状態(2 から始まる状態番号)
+    Dying(u8),
+}
+impl CellState {
+    /// ルールで使う状態番号(死 0, 生 1, 消えかけ 2 以上)から作ります。
+    fn from_index(index: u8) -> Self {
+        match index {
+            0 => CellState::Dead,
+            1 => CellState::Live,
+            n => CellState::Dying(n),
+        }
+    }
+    /// ルールで使う状態番号を返します。
+    fn index(self) -> u8 {
+        match self {
+            CellState::Dead => 0,
+            CellState::Live => 1,
+            CellState::Dying(n) => n,
+        }
+    }
+    /// 状態数 states のルールで、この状態の表示に使う文字を返します。死んだセルは dead にします。
+    fn glyph(self, states: usize, dead: char) -> char {
+        match self {
+            CellState::Dead => dead,
+            CellState::Live => '*',
+            CellState::Dying(n) => {
+                DYING_GLYPHS[(n as usize - 2) * DYING_GLYPHS.len() / (states - 2).max(1)]
+            }
+        }
+    }
 }
 #[derive(Debug, Clone)]
 struct Cell {
     now_state: CellState,
-    count: usize,
+    /// 自分と周りを含めた 3x3 の範囲の生存セルの並び(rule::neighbourhood で作る番号)
+    /// Larger than Life のルールでは、自分を含めた範囲内の生存セルの数
+    neighbourhood: u16,
 }
 impl Cell {
     fn new() -> Self {
         Cell {
             now_state: CellState::Dead,
-            count: 0,
+            neighbourhood: 0,
         }
     }
-    fn touch(&mut self) {
-        self.count += 1
-    }
-    fn untouch(&mut self) {
-        self.count -= 1
-    }
     fn is_live(&self) -> bool {
         self.now_state == CellState::Live
     }
-    fn commit_state(&mut self) {
-        self.now_state = match self.count {
-            3 => CellState::Live,
-            2 => self.now_state.clone(),
-            _ => CellState::Dead,
-        };
-        self.count = 0;
+    fn commit_state(&mut self, rule: &Rule) {
+        self.now_state =
+            CellState::from_index(rule.next(self.now_state.index(), self.neighbourhood));
+        self.neighbourhood = 0;
+    }
+    fn clear(&mut self) {
+        self.now_state = CellState::Dead;
+        self.neighbourhood = 0;
     }
     fn set_state(&mut self, state: CellState) {
         self.now_state = state;
     }
 }
 
+/// 盤面の大きさが決まらないときの幅と高さ
+const DEFAULT_SIZE: usize = 25;
+/// apgcode で指定したパターンの周りに空ける余白のセル数
+const APGCODE_MARGIN: usize = 10;
+
+/// パターンファイルもランダム配置も指定されなかったときの初期配置(盤面中央からの相対座標)
+const START_PATTERN: [(usize, usize); 8] = [
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (1, 2),
+    (6, 0),
+    (6, 1),
+    (7, 1),
+    (6, 2),
+];
+
 fn main() {
-    let mut board = Board::new(25, 25, 100);
-    let (mut x, mut y) = board.get_board_size();
-    x /= 2;
-    y /= 2;
-    board.show_board();
-    board.set_live(
-        [
-            (x + 1, y + 0),
-            (x + 1, y + 1),
-            (x + 0, y + 1),
-            (x + 1, y + 2),
-            (x + 6, y + 0),
-            (x + 6, y + 1),
-            (x + 7, y + 1),
-            (x + 6, y + 2),
-        ]
-        .to_vec(),
-    );
+    let config = match Config::parse(std::env::args().skip(1)) {
+        Ok(config) => config,
+        Err(CliError::Help) => {
+            println!("{}", cli::USAGE);
+            return;
+        }
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+    let pattern = config.pattern.as_ref().map(|path| {
+        Pattern::load(path).unwrap_or_else(|e| {
+            eprintln!("{}: {}", path.display(), e);
+            std::process::exit(1);
+        })
+    });
+    let pattern = pattern.or_else(|| {
+        config.apgcode.as_ref().map(|code| {
+            Pattern::from_apgcode(code).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            })
+        })
+    });
+    let setup = Setup::new(&config, pattern.as_ref()).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    if !setup.rule.is_life_like() && (config.engine != Engine::Dense || config.census.is_some()) {
+        eprintln!(
+            "Generations 系や Larger than Life、六角形・フォン・ノイマン近傍のルール ({}) は --engine dense でのみ使え、--census とは一緒に使えません",
+            setup.rule
+        );
+        std::process::exit(1);
+    }
+    if !setup.rule.is_totalistic() && config.engine == Engine::Bitboard {
+        eprintln!(
+            "周りの並びで決まるルール ({}) は --engine bitboard では使えません",
+            setup.rule
+        );
+        std::process::exit(1);
+    }
+    // 六角形の近傍は行の偶奇で数えるセルが変わるので、高さが奇数だと上下の端をまたぐところで偶奇がそろわない
+    if setup.rule.neighbourhood() == Neighbourhood::Hexagonal
+        && setup.topology == Topology::Torus
+        && setup.height % 2 == 1
+    {
+        eprintln!(
+            "六角形の近傍のルール ({}) を --topology torus で使うときは、盤面の高さ ({}) を偶数にしてください",
+            setup.rule, setup.height
+        );
+        std::process::exit(1);
+    }
+    if setup.rule.has_b0() && matches!(config.engine, Engine::Sparse | Engine::HashLife) {
+        eprintln!(
+            "B0 を含むルール ({}) は --engine sparse や hashlife では使えません",
+            setup.rule
+        );
+        std::process::exit(1);
+    }
+    let mut stats = config.stats.as_ref().map(|path| match File::create(path) {
+        Ok(file) => StatsLog::new(BufWriter::new(file)),
+        Err(e) => {
+            eprintln!("{}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+    });
 
-    loop {
-        board.reflesh_state();
-        board.commit_state();
-        board.show_board();
-        if board.is_done() {
-            break;
+    // 最後の盤面をパターンにするのは、使うときだけにする(大きな盤面では生存セルの座標だけでも大きい)
+    let keep_result = config.census.is_some() || config.save.is_some();
+    let (mut summary, result) = match config.engine {
+        Engine::Dense if config.output == Output::Interactive => {
+            let mut board = setup.board(config.history);
+            board.set_threads(config.threads);
+            board.enable_rewind(config.rewind.unwrap_or(tui::DEFAULT_REWIND));
+            let mut summary = tui::run(&mut board, &config).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            });
+            summary.tiles = Some(board.tile_stats());
+            (summary, keep_result.then(|| board.to_pattern()))
         }
+        Engine::Dense => {
+            let mut board = setup.board(config.history);
+            board.set_threads(config.threads);
+            if let Some(capacity) = config.rewind {
+                board.enable_rewind(capacity);
+            }
+            let mut summary = run::run(&mut board, &config, stats.as_mut());
+            summary.tiles = Some(board.tile_stats());
+            (summary, keep_result.then(|| board.to_pattern()))
+        }
+        Engine::Bitboard => {
+            let mut bits = setup.bitboard(config.history);
+            (
+                run::run(&mut bits, &config, stats.as_mut()),
+                keep_result.then(|| bits.to_pattern()),
+            )
+        }
+        Engine::Sparse => {
+            let mut sparse = setup.sparse(config.history);
+            (
+                run::run(&mut sparse, &config, stats.as_mut()),
+                keep_result.then(|| sparse.to_pattern()),
+            )
+        }
+        Engine::HashLife => {
+            // HashLife は途中の世代を表示せず、最後の世代まで一気に進める
+            let mut life = setup.hashlife();
+            let initial_population = life.population();
+            life.advance(config.max_generations.unwrap_or(0));
+            if config.output != Output::None {
+                life.to_board(setup.width, setup.height, config.history)
+                    .show_board();
+            }
+            let cells = life.live_cells();
+            let summary = RunSummary {
+                outcome: if life.population() == 0 {
+                    RunOutcome::Extinct
+                } else {
+                    RunOutcome::MaxGenerations
+                },
+                cycle_start: None,
+                generations: life.generation(),
+                population: life.population(),
+                // 途中の世代は計算しないので、最初と最後の大きい方になる
+                peak_population: initial_population.max(life.population()),
+                bounding_box: summary::bounding_box(cells.iter().copied()),
+                seed: None,
+                census: None,
+                apgcode: None,
+                tiles: None,
+            };
+            (
+                summary,
+                keep_result.then(|| Pattern::from_points(cells, Some(life.rule().clone()))),
+            )
+        }
+    };
+    summary.seed = setup.seed();
+    if let (Some(stats), Some(path)) = (stats, &config.stats) {
+        if let Err(e) = stats.finish() {
+            eprintln!("{}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+    }
+    if let (Some(distance), Some(result)) = (config.census, &result) {
+        let cells: Vec<(i64, i64)> = result
+            .cells
+            .iter()
+            .map(|&(x, y)| (x as i64, y as i64))
+            .collect();
+        let rule = result.rule.clone().unwrap_or_default();
+        summary.census = Some(Census::take(&cells, &rule, distance));
+        summary.apgcode = result.apgcode();
+    }
+    match config.summary {
+        SummaryFormat::Text => println!("{}", summary),
+        SummaryFormat::Json => println!("{}", summary.to_json()),
+        SummaryFormat::None => {}
+    }
+
+    if let (Some(path), Some(result)) = (&config.save, &result) {
+        if let Err(e) = result.save(path) {
+            eprintln!("{}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// 設定とパターンから決めた初期状態です。どの計算方法の盤面も、これから作ります。
+struct Setup {
+    width: usize,
+    height: usize,
+    rule: Rule,
+    topology: Topology,
+    /// ランダムに埋める場合のスープ
+    soup: Option<Soup>,
+    /// パターン(なければ最初の配置)を盤面に置いたときの生存セル
+    cells: Vec<(usize, usize)>,
+    /// パターンを盤面に置いたときの消えかけのセル (x, y, 状態番号)
+    dying: Vec<(usize, usize, u8)>,
+}
+impl Setup {
+    /// 盤面の幅か高さが 0 になる場合(大きさ 0 のパターンファイルなど)はエラーにします。
+    fn new(config: &Config, pattern: Option<&Pattern>) -> Result<Self, String> {
+        let (pattern_width, pattern_height) = match pattern {
+            // apgcode の形は生存セルを囲む最小の矩形なので、そのままでは振動子や宇宙船が動けない
+            Some(p) if config.apgcode.is_some() => (
+                (p.width + 2 * APGCODE_MARGIN).max(DEFAULT_SIZE),
+                (p.height + 2 * APGCODE_MARGIN).max(DEFAULT_SIZE),
+            ),
+            Some(p) => (p.width, p.height),
+            None => (DEFAULT_SIZE, DEFAULT_SIZE),
+        };
+        let width = config.width.unwrap_or(pattern_width);
+        let height = config.height.unwrap_or(pattern_height);
+        if width == 0 || height == 0 {
+            return Err(format!(
+                "盤面の大きさが {} x {} になります。--width と --height で 1 以上の大きさを指定してください",
+                width, height
+            ));
+        }
+        let rule = config
+            .rule
+            .clone()
+            .or_else(|| pattern.and_then(|p| p.rule.clone()))
+            .unwrap_or_default();
+        let soup = config.density.map(|density| Soup {
+            density,
+            region: config.region,
+            symmetry: config.symmetry,
+            seed: config.seed.unwrap_or_else(Random::seed_from_time),
+        });
+        // パターンは盤面の中央に置き、はみ出した部分は捨てる
+        let (dx, dy) = match pattern {
+            Some(p) => (
+                width.saturating_sub(p.width) / 2,
+                height.saturating_sub(p.height) / 2,
+            ),
+            None => (0, 0),
+        };
+        let inside = |x: usize, y: usize| x < width && y < height;
+        let cells = match pattern {
+            Some(pattern) => pattern
+                .cells
+                .iter()
+                .map(|&(x, y)| (x + dx, y + dy))
+                .filter(|&(x, y)| inside(x, y))
+                .collect(),
+            None if soup.is_none() => {
+                let (x, y) = (width / 2, height / 2);
+                START_PATTERN
+                    .iter()
+                    .map(|&(dx, dy)| (x + dx, y + dy))
+                    .filter(|&(x, y)| inside(x, y))
+                    .collect()
+            }
+            None => Vec::new(),
+        };
+        let dying = pattern
+            .iter()
+            .flat_map(|pattern| &pattern.dying)
+            .map(|&(x, y, state)| (x + dx, y + dy, state))
+            .filter(|&(x, y, _)| inside(x, y))
+            .collect();
+        Ok(Setup {
+            width,
+            height,
+            rule,
+            topology: config.topology,
+            soup,
+            cells,
+            dying,
+        })
+    }
+    /// ランダムに埋めた場合は、使ったシードを返します。
+    fn seed(&self) -> Option<u64> {
+        self.soup.map(|soup| soup.seed)
+    }
+    /// 生存セルの座標を返します。同じ座標が2回以上返ることがあります。
+    fn live_cells(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let soup = self
+            .soup
+            .iter()
+            .flat_map(|soup| soup.cells(self.width, self.height));
+        soup.chain(self.cells.iter().copied())
+    }
+    /// Board を作ります。
+    fn board(&self, board_histories: usize) -> Board {
+        let mut board = Board::new(self.width, self.height, board_histories);
+        board.set_rule(self.rule.clone());
+        board.set_topology(self.topology);
+        board.set_live(self.live_cells());
+        board.set_dying(self.dying.iter().copied());
+        board
+    }
+    /// Board を経由せずに BitBoard を作ります。
+    fn bitboard(&self, board_histories: usize) -> BitBoard {
+        let mut bits = BitBoard::new(self.width, self.height, board_histories);
+        bits.set_rule(self.rule.clone());
+        bits.set_topology(self.topology);
+        bits.set_live(self.live_cells());
+        bits
+    }
+    /// 生存セルとルールを SparseBoard に移します。
+    fn sparse(&self, board_histories: usize) -> SparseBoard {
+        let mut sparse = SparseBoard::new(board_histories);
+        sparse.set_rule(self.rule.clone());
+        sparse.set_live(
+            self.live_cells()
+                .map(|(x, y)| (x as i64, y as i64))
+                .collect(),
+        );
+        sparse
+    }
+    /// 生存セルとルールを置いた Board から HashLife を作ります。
+    fn hashlife(&self) -> HashLife {
+        HashLife::from_board(&self.board(1))
     }
 }
@@ -0,0 +1,219 @@
+//! apgcode (拡張 Wechsler 形式) の読み書きを行います。
+//!
+//! ```text
+//! xs4_33     block (固定物、生存セル 4)
+//! xp2_7      blinker (周期 2 の振動子)
+//! xq4_153    glider (周期 4 の宇宙船)
+//! ```
+//!
+//! "_" の前は種類と数(固定物は生存セル数、振動子と宇宙船は周期)で、後ろは形です。
+//! 形は上から5行ずつの帯に分け、帯ごとに各列の5セルを32進数の1文字
+//! (上のセルが最下位ビット)で表し、帯の間を 'z' で区切ります。
+//! 続く空の列は 'w' (2列)、'x' (3列)、'y' と36進数の1文字 (4〜39列) に縮め、帯の末尾の空の列は省きます。
+
+use super::PatternError;
+use crate::{
+    census::{self, ObjectKind},
+    rule::Rule,
+};
+
+/// 列を表す文字
+const DIGITS: &[u8; 32] = b"0123456789abcdefghijklmnopqrstuv";
+/// 'y' の後で、空の列の数から 4 を引いた数を表す文字
+const GAP_DIGITS: &[u8; 36] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+/// 帯の高さ
+const STRIP: i64 = 5;
+
+/// 形(左上が (0, 0))を Wechsler 形式の文字列にします。
+pub fn encode(cells: &[(i64, i64)]) -> String {
+    let width = cells.iter().map(|&(x, _)| x + 1).max().unwrap_or(0) as usize;
+    let height = cells.iter().map(|&(_, y)| y + 1).max().unwrap_or(0);
+    let strips = (height.max(1) + STRIP - 1) / STRIP;
+    let mut columns = vec![vec![0u8; width]; strips as usize];
+    for &(x, y) in cells {
+        columns[(y / STRIP) as usize][x as usize] |= 1 << (y % STRIP);
+    }
+    let strips: Vec<String> = columns
+        .iter()
+        .map(|strip| {
+            let mut text = String::new();
+            let mut zeros = 0;
+            for &column in strip {
+                if column == 0 {
+                    zeros += 1;
+                    continue;
+                }
+                push_zeros(&mut text, zeros);
+                zeros = 0;
+                text.push(DIGITS[column as usize] as char);
+            }
+            text
+        })
+        .collect();
+    let text = strips.join("z");
+    if text.is_empty() {
+        "0".to_string()
+    } else {
+        text
+    }
+}
+
+/// zeros 列分の空の列を、縮めた文字で書き足します。
+fn push_zeros(text: &mut String, mut zeros: usize) {
+    while zeros >= 40 {
+        text.push_str("yz");
+        zeros -= 39;
+    }
+    match zeros {
+        0 => {}
+        1 => text.push('0'),
+        2 => text.push('w'),
+        3 => text.push('x'),
+        n => {
+            text.push('y');
+            text.push(GAP_DIGITS[n - 4] as char);
+        }
+    }
+}
+
+/// Wechsler 形式の文字列から形を作ります。
+fn decode(text: &str) -> Result<Vec<(i64, i64)>, PatternError> {
+    let digit = |c: char| DIGITS.iter().position(|&d| d as char == c);
+    let gap = |c: char| GAP_DIGITS.iter().position(|&d| d as char == c);
+    let mut cells = Vec::new();
+    let (mut x, mut y0) = (0, 0);
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            'z' => {
+                x = 0;
+                y0 += STRIP;
+            }
+            'w' => x += 2,
+            'x' => x += 3,
+            'y' => match chars.next().and_then(gap) {
+                Some(n) => x += 4 + n as i64,
+                None => {
+                    return Err(PatternError::Apgcode(
+                        "'y' の後に列数がありません".to_string(),
+                    ))
+                }
+            },
+            c => match digit(c) {
+                Some(column) => {
+                    for bit in 0..STRIP {
+                        if column >> bit & 1 == 1 {
+                            cells.push((x, y0 + bit));
+                        }
+                    }
+                    x += 1;
+                }
+                None => return Err(PatternError::Apgcode(format!("不正な文字です: {}", c))),
+            },
+        }
+    }
+    Ok(cells)
+}
+
+/// apgcode を解析して、生存セルの座標を返します。
+pub fn parse(code: &str) -> Result<Vec<(i64, i64)>, PatternError> {
+    let invalid = || PatternError::Apgcode(format!("xs / xp / xq で始まっていません: {}", code));
+    let (prefix, body) = code.split_once('_').ok_or_else(invalid)?;
+    match prefix.get(..2) {
+        Some("xs" | "xp" | "xq") if prefix[2..].parse::<u64>().is_ok() => decode(body),
+        _ => Err(invalid()),
+    }
+}
+
+/// evolve で調べた種類と位相から apgcode を作ります。種類が分からなければ None を返します。
+/// 形は、すべての位相と向きのうち、最も短く、同じ長さなら辞書順で最も小さいものを選びます。
+pub fn from_phases(kind: ObjectKind, phases: &[Vec<(i64, i64)>]) -> Option<String> {
+    let prefix = match kind {
+        ObjectKind::StillLife => format!("xs{}", phases.first()?.len()),
+        ObjectKind::Oscillator { period } => format!("xp{}", period),
+        ObjectKind::Spaceship { period, .. } => format!("xq{}", period),
+        ObjectKind::Unknown => return None,
+    };
+    let body = phases
+        .iter()
+        .flat_map(|phase| census::orientations(phase))
+        .map(|shape| encode(&shape))
+        .min_by(|a, b| a.len().cmp(&b.len()).then(a.cmp(b)))?;
+    Some(format!("{}_{}", prefix, body))
+}
+
+/// 生存セルの座標 cells の apgcode を返します。周期が長すぎるなどで種類が分からなければ None を返します。
+pub fn apgcode(cells: &[(i64, i64)], rule: &Rule) -> Option<String> {
+    if cells.is_empty() {
+        return Some("xs0_0".to_string());
+    }
+    let (kind, phases) = census::evolve(cells, rule);
+    from_phases(kind, &phases)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 形を並べ替えて比べられるようにします。
+    fn sorted(mut cells: Vec<(i64, i64)>) -> Vec<(i64, i64)> {
+        cells.sort();
+        cells
+    }
+
+    /// 2つのブロックを gap 列空けて並べた形です。
+    fn two_blocks(gap: i64) -> Vec<(i64, i64)> {
+        let right = gap + 2;
+        sorted(vec![
+            (0, 0),
+            (1, 0),
+            (0, 1),
+            (1, 1),
+            (right, 0),
+            (right + 1, 0),
+            (right, 1),
+            (right + 1, 1),
+        ])
+    }
+
+    #[test]
+    fn round_trips_gaps() {
+        for gap in 0..=100 {
+            let cells = two_blocks(gap);
+            let text = encode(&cells);
+            assert_eq!(
+                sorted(decode(&text).unwrap()),
+                cells,
+                "{} 列: {}",
+                gap,
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn encodes_long_gaps_in_base36() {
+        assert_eq!(encode(&two_blocks(4)), "33y033");
+        assert_eq!(encode(&two_blocks(38)), "33yy33");
+        assert_eq!(encode(&two_blocks(39)), "33yz33");
+        assert_eq!(encode(&two_blocks(40)), "33yz033");
+    }
+
+    #[test]
+    fn round_trips_multiple_strips() {
+        // 5行を超える形は 'z' で帯を区切る
+        let cells = vec![(0, 0), (3, 4), (1, 5), (2, 9), (45, 7), (0, 12)];
+        let text = encode(&cells);
+        assert_eq!(sorted(decode(&text).unwrap()), sorted(cells));
+    }
+
+    #[test]
+    fn parses_codes() {
+        assert_eq!(sorted(parse("xs4_33").unwrap()), two_blocks(0)[..4]);
+        assert_eq!(parse("xs8_33yzy533").unwrap().len(), 8);
+        assert!(parse("xs8_33yzy533").unwrap().contains(&(50, 1)));
+        assert!(parse("xs4_33y").is_err());
+        assert!(parse("xs4_3A").is_err());
+        assert!(parse("ys4_33").is_err());
+    }
+}
@@ -0,0 +1,101 @@
+//! Life 1.06 形式の読み書きを行います。
+//!
+//! ```text
+//! #Life 1.06
+//! 0 -1
+//! 1 0
+//! -1 1
+//! 0 1
+//! 1 1
+//! ```
+
+use super::{Pattern, PatternError};
+
+const HEADER: &str = "#Life 1.06";
+
+/// Life 1.06 形式の文字列を解析します。
+/// 座標は負の値も取れるため、左上が (0, 0) になるようにずらします。
+pub fn parse(text: &str) -> Result<Pattern, PatternError> {
+    let mut comments = Vec::new();
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()));
+    match lines.next() {
+        Some((_, HEADER)) => {}
+        _ => {
+            return Err(PatternError::syntax(
+                1,
+                format!("1行目が \"{}\" ではありません", HEADER),
+            ))
+        }
+    }
+    let mut points = Vec::new();
+    for (line_no, line) in lines {
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(comment) = line.strip_prefix('#') {
+            comments.push(comment.to_string());
+            continue;
+        }
+        let coordinates: Vec<i64> = line
+            .split_whitespace()
+            .map(|n| n.parse())
+            .collect::<Result<_, _>>()
+            .map_err(|_| PatternError::syntax(line_no, format!("座標が不正です: {}", line)))?;
+        match coordinates[..] {
+            [x, y] => points.push((x, y)),
+            _ => {
+                return Err(PatternError::syntax(
+                    line_no,
+                    format!("座標は \"x y\" の形で書いてください: {}", line),
+                ))
+            }
+        }
+    }
+    Ok(Pattern {
+        comments,
+        ..Pattern::from_points(points, None)
+    })
+}
+
+/// パターンを Life 1.06 形式の文字列にします。
+pub fn write(pattern: &Pattern) -> String {
+    let mut out = format!("{}\n", HEADER);
+    for comment in &pattern.comments {
+        out += &format!("#{}\n", comment);
+    }
+    for &(x, y) in &pattern.cells {
+        out += &format!("{} {}\n", x, y);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_comments_and_cells() {
+        let text = "#Life 1.06\n#D Glider\n#D 2 行目\n1 0\n2 1\n0 2\n1 2\n2 2\n";
+        let pattern = parse(text).unwrap();
+        assert_eq!(pattern.comments, ["D Glider", "D 2 行目"]);
+        assert_eq!((pattern.width, pattern.height), (3, 3));
+        assert_eq!(write(&pattern), text);
+    }
+
+    #[test]
+    fn reports_line_of_bad_coordinates() {
+        for (text, line) in [
+            ("#Life 1.05\n0 0\n", 1),
+            ("#Life 1.06\n0 0\n\n1 x\n", 4),
+            ("#Life 1.06\n#D\n0 0 0\n", 3),
+        ] {
+            match parse(text) {
+                Err(PatternError::Syntax { line: l, .. }) => assert_eq!(l, line, "{:?}", text),
+                other => panic!("{:?} の結果が {:?}", text, other),
+            }
+        }
+    }
+}
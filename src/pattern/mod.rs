@@ -0,0 +1,182 @@
+//! パターンファイルの読み書きを行います。
+
+pub mod apgcode;
+pub mod life106;
+pub mod plaintext;
+pub mod rle;
+
+use crate::rule::{Rule, RuleError};
+use crate::Board;
+use std::{fmt, fs, io, path::Path};
+
+/// パターンファイルから読み込んだ盤面の内容です。
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Pattern {
+    pub width: usize,
+    pub height: usize,
+    /// ファイルにルールが書かれていなければ None
+    pub rule: Option<Rule>,
+    /// 生存セルの座標(左上が (0, 0))
+    pub cells: Vec<(usize, usize)>,
+    /// Generations 系のルールで、消えかけのセルの (x, y, 状態番号)。状態番号は 2 以上
+    pub dying: Vec<(usize, usize, u8)>,
+    pub comments: Vec<String>,
+}
+impl Pattern {
+    /// 座標の一覧から、左上が (0, 0) になるようにずらしたパターンを作ります。
+    pub fn from_points(
+        points: impl IntoIterator<Item = (i64, i64)>,
+        rule: Option<Rule>,
+    ) -> Pattern {
+        let points: Vec<(i64, i64)> = points.into_iter().collect();
+        let mut pattern = Pattern {
+            rule,
+            ..Pattern::default()
+        };
+        if let (Some(min_x), Some(min_y)) = (
+            points.iter().map(|&(x, _)| x).min(),
+            points.iter().map(|&(_, y)| y).min(),
+        ) {
+            for (x, y) in points {
+                let (x, y) = ((x - min_x) as usize, (y - min_y) as usize);
+                pattern.width = pattern.width.max(x + 1);
+                pattern.height = pattern.height.max(y + 1);
+                pattern.cells.push((x, y));
+            }
+        }
+        pattern
+    }
+    /// apgcode (xs4_33 など) からパターンを作ります。
+    pub fn from_apgcode(code: &str) -> Result<Pattern, PatternError> {
+        Ok(Pattern::from_points(apgcode::parse(code)?, None))
+    }
+    /// パターンの apgcode を返します。ルールが書かれていなければ B3/S23 として調べます。
+    /// 周期が長すぎるなどで種類が分からなければ None を返します。
+    pub fn apgcode(&self) -> Option<String> {
+        let cells: Vec<(i64, i64)> = self
+            .cells
+            .iter()
+            .map(|&(x, y)| (x as i64, y as i64))
+            .collect();
+        apgcode::apgcode(&cells, &self.rule.clone().unwrap_or_default())
+    }
+    /// ファイルを読み込んでパターンを返します。形式は拡張子で判断します。
+    pub fn load(path: impl AsRef<Path>) -> Result<Pattern, PatternError> {
+        let format = Format::from_path(path.as_ref());
+        let text = fs::read_to_string(path).map_err(PatternError::Io)?;
+        format.parse(&text)
+    }
+    /// パターンをファイルに書き出します。形式は拡張子で判断します。
+    /// 消えかけのセルは RLE 形式でしか書けないので、ほかの形式では書き出さずにエラーにします。
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), PatternError> {
+        let format = Format::from_path(path.as_ref());
+        if !self.dying.is_empty() && format != Format::Rle {
+            return Err(PatternError::DyingCells);
+        }
+        fs::write(path, format.write(self)).map_err(PatternError::Io)
+    }
+}
+
+/// パターンファイルの形式です。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Rle,
+    /// Plaintext(.cells)
+    Plaintext,
+    Life106,
+}
+impl Format {
+    /// 拡張子から形式を判断します。分からなければ RLE として扱います。
+    pub fn from_path(path: &Path) -> Format {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("cells") => Format::Plaintext,
+            Some("lif" | "life") => Format::Life106,
+            _ => Format::Rle,
+        }
+    }
+    pub fn parse(self, text: &str) -> Result<Pattern, PatternError> {
+        match self {
+            Format::Rle => rle::parse(text),
+            Format::Plaintext => plaintext::parse(text),
+            Format::Life106 => life106::parse(text),
+        }
+    }
+    pub fn write(self, pattern: &Pattern) -> String {
+        match self {
+            Format::Rle => rle::write(pattern),
+            Format::Plaintext => plaintext::write(pattern),
+            Format::Life106 => life106::write(pattern),
+        }
+    }
+}
+
+/// パターンファイルの読み書きに失敗した理由です。
+#[derive(Debug)]
+pub enum PatternError {
+    Io(io::Error),
+    /// line 行目の書式が不正
+    Syntax {
+        line: usize,
+        message: String,
+    },
+    /// line 行目のルール文字列が不正
+    Rule {
+        line: usize,
+        error: RuleError,
+    },
+    /// apgcode の書式が不正
+    Apgcode(String),
+    /// 消えかけのセルを書けない形式で保存しようとした
+    DyingCells,
+}
+impl PatternError {
+    fn syntax(line: usize, message: impl Into<String>) -> Self {
+        PatternError::Syntax {
+            line,
+            message: message.into(),
+        }
+    }
+}
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatternError::Io(e) => write!(f, "{}", e),
+            PatternError::Syntax { line, message } => write!(f, "{}行目: {}", line, message),
+            PatternError::Rule { line, error } => write!(f, "{}行目: {}", line, error),
+            PatternError::Apgcode(message) => write!(f, "apgcode: {}", message),
+            PatternError::DyingCells => write!(
+                f,
+                "消えかけのセルがある盤面は RLE 形式(.rle)でしか保存できません"
+            ),
+        }
+    }
+}
+impl std::error::Error for PatternError {}
+
+impl Board {
+    /// 盤面の大きさ・ルール・生存セル・消えかけのセルをパターンとして取り出します。
+    pub fn to_pattern(&self) -> Pattern {
+        let (mut cells, mut dying) = (Vec::new(), Vec::new());
+        for y in 0..self.height {
+            for x in 0..self.width {
+                match self.array[y + 1][x + 1].now_state.index() {
+                    0 => {}
+                    1 => cells.push((x, y)),
+                    state => dying.push((x, y, state)),
+                }
+            }
+        }
+        Pattern {
+            width: self.width,
+            height: self.height,
+            rule: Some(self.rule.clone()),
+            cells,
+            dying,
+            comments: Vec::new(),
+        }
+    }
+}
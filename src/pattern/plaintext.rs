@@ -0,0 +1,81 @@
+//! Plaintext(.cells)形式の読み書きを行います。
+//!
+//! ```text
+//! !Name: Glider
+//! .O.
+//! ..O
+//! OOO
+//! ```
+
+use super::{Pattern, PatternError};
+
+/// Plaintext 形式の文字列を解析します。
+pub fn parse(text: &str) -> Result<Pattern, PatternError> {
+    let mut pattern = Pattern::default();
+    let mut y = 0;
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim_end();
+        if let Some(comment) = line.strip_prefix('!') {
+            pattern.comments.push(comment.to_string());
+            continue;
+        }
+        for (x, c) in line.chars().enumerate() {
+            match c {
+                '.' => {}
+                'O' | '*' => pattern.cells.push((x, y)),
+                c => {
+                    return Err(PatternError::syntax(
+                        i + 1,
+                        format!("不正な文字です: '{}'", c),
+                    ))
+                }
+            }
+        }
+        pattern.width = pattern.width.max(line.chars().count());
+        y += 1;
+        // 末尾の空行は盤面に含めない
+        if !line.is_empty() {
+            pattern.height = y;
+        }
+    }
+    Ok(pattern)
+}
+
+/// パターンを Plaintext 形式の文字列にします。
+pub fn write(pattern: &Pattern) -> String {
+    let mut out = String::new();
+    for comment in &pattern.comments {
+        out += &format!("!{}\n", comment);
+    }
+    let mut grid = vec![vec!['.'; pattern.width]; pattern.height];
+    for &(x, y) in &pattern.cells {
+        grid[y][x] = 'O';
+    }
+    for row in grid {
+        out.extend(row);
+        out += "\n";
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_comments_and_cells() {
+        let text = "!Name: Glider\n.O.\n..O\nOOO\n";
+        let pattern = parse(text).unwrap();
+        assert_eq!(pattern.comments, ["Name: Glider"]);
+        assert_eq!(pattern.cells, [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)]);
+        assert_eq!(write(&pattern), text);
+    }
+
+    #[test]
+    fn reports_line_of_bad_character() {
+        match parse("!Name: Glider\n.O.\n..X\nOOO\n") {
+            Err(PatternError::Syntax { line, .. }) => assert_eq!(line, 3),
+            other => panic!("{:?}", other),
+        }
+    }
+}
@@ -0,0 +1,272 @@
+//! RLE 形式の読み書きを行います。
+//!
+//! ```text
+//! #N Glider
+//! x = 3, y = 3, rule = B3/S23
+//! bob$2bo$3o!
+//! ```
+//!
+//! Generations 系のルールでは、死を '.'、生を 'A'、消えかけの状態 2, 3, … を 'B', 'C', … で書きます。
+//! 状態 25 以降は 'p'〜'y' を前に付けて、'pA' が 25、'pB' が 26、… のように表します。
+//!
+//! ```text
+//! x = 4, y = 1, rule = 345/2/4
+//! .ABC!
+//! ```
+
+use super::{Pattern, PatternError};
+use crate::rule::Rule;
+
+/// 1行の最大文字数(本体部分)
+const LINE_LENGTH: usize = 70;
+/// 複数状態の書き方で、前に付ける1文字ごとに進む状態番号の数
+const STATES_PER_PREFIX: usize = 24;
+
+/// RLE 形式の文字列を解析します。
+pub fn parse(text: &str) -> Result<Pattern, PatternError> {
+    let mut pattern = Pattern::default();
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()));
+
+    // ヘッダ行より前は '#' で始まるコメント行
+    let (line_no, header) = loop {
+        match lines.next() {
+            Some((_, "")) => continue,
+            Some((_, line)) if line.starts_with('#') => {
+                pattern.comments.push(line[1..].to_string())
+            }
+            Some(header) => break header,
+            None => return Err(PatternError::syntax(1, "ヘッダ行がありません")),
+        }
+    };
+    parse_header(line_no, header, &mut pattern)?;
+
+    let (mut x, mut y) = (0, 0);
+    let mut count: Option<usize> = None;
+    // 複数状態の書き方で、直前に読んだ 'p'〜'y' の番号(1 から)
+    let mut prefix: Option<usize> = None;
+    'body: for (line_no, line) in lines {
+        if line.starts_with('#') {
+            continue;
+        }
+        for c in line.chars() {
+            if let Some(digit) = c.to_digit(10) {
+                count = count
+                    .unwrap_or(0)
+                    .checked_mul(10)
+                    .and_then(|n| n.checked_add(digit as usize));
+                if count.is_none() {
+                    return Err(PatternError::syntax(line_no, "繰り返し回数が大きすぎます"));
+                }
+                continue;
+            }
+            if c.is_whitespace() {
+                continue;
+            }
+            if prefix.is_none() && ('p'..='y').contains(&c) {
+                prefix = Some(c as usize - 'p' as usize + 1);
+                continue;
+            }
+            let run = count.take().unwrap_or(1);
+            let state = match (prefix.take(), c) {
+                (None, 'b' | '.') => 0,
+                (None, 'o') => 1,
+                (prefix, 'A'..='X') => {
+                    let state =
+                        prefix.unwrap_or(0) * STATES_PER_PREFIX + (c as usize - 'A' as usize + 1);
+                    u8::try_from(state).map_err(|_| {
+                        PatternError::syntax(line_no, format!("状態番号が大きすぎます: {}", state))
+                    })?
+                }
+                (None, '$') => {
+                    x = 0;
+                    y += run;
+                    continue;
+                }
+                (None, '!') => break 'body,
+                (_, c) => {
+                    return Err(PatternError::syntax(
+                        line_no,
+                        format!("不正な文字です: '{}'", c),
+                    ))
+                }
+            };
+            match state {
+                0 => {}
+                1 => pattern.cells.extend((x..x + run).map(|x| (x, y))),
+                state => pattern.dying.extend((x..x + run).map(|x| (x, y, state))),
+            }
+            x += run;
+            if state != 0 {
+                pattern.width = pattern.width.max(x);
+                pattern.height = pattern.height.max(y + 1);
+            }
+        }
+    }
+    Ok(pattern)
+}
+
+/// "x = 3, y = 3, rule = B3/S23" 形式のヘッダ行を解析します。
+fn parse_header(line_no: usize, header: &str, pattern: &mut Pattern) -> Result<(), PatternError> {
+    let (mut width, mut height) = (None, None);
+    // Larger than Life のルールは ',' を含むので、rule から行末までをまとめてルールとする
+    let (header, rule) = match header.to_ascii_lowercase().find("rule") {
+        Some(i) => (
+            header[..i].trim_end().trim_end_matches(','),
+            Some(&header[i..]),
+        ),
+        None => (header, None),
+    };
+    for item in header.split(',').chain(rule) {
+        let (key, value) = item.split_once('=').ok_or_else(|| {
+            PatternError::syntax(line_no, format!("ヘッダ行が不正です: {}", item))
+        })?;
+        let value = value.trim();
+        let size = || {
+            value
+                .parse::<usize>()
+                .map_err(|_| PatternError::syntax(line_no, format!("大きさが不正です: {}", value)))
+        };
+        match key.trim().to_ascii_lowercase().as_str() {
+            "x" => width = Some(size()?),
+            "y" => height = Some(size()?),
+            "rule" => {
+                let rule = value.parse::<Rule>().map_err(|error| PatternError::Rule {
+                    line: line_no,
+                    error,
+                })?;
+                pattern.rule = Some(rule);
+            }
+            // 知らない項目は無視する
+            _ => {}
+        }
+    }
+    match (width, height) {
+        (Some(width), Some(height)) => {
+            pattern.width = width;
+            pattern.height = height;
+            Ok(())
+        }
+        _ => Err(PatternError::syntax(
+            line_no,
+            "ヘッダ行に x と y がありません",
+        )),
+    }
+}
+
+/// パターンを RLE 形式の文字列にします。
+pub fn write(pattern: &Pattern) -> String {
+    let mut out = String::new();
+    for comment in &pattern.comments {
+        out += &format!("#{}\n", comment);
+    }
+    out += &format!("x = {}, y = {}", pattern.width, pattern.height);
+    if let Some(rule) = &pattern.rule {
+        out += &format!(", rule = {}", rule);
+    }
+    out += "\n";
+
+    // Generations 系のルールか消えかけのセルがあれば、複数状態の書き方にする
+    let multi_state =
+        !pattern.dying.is_empty() || pattern.rule.as_ref().is_some_and(|rule| rule.states() > 2);
+    let mut grid = vec![vec![0u8; pattern.width]; pattern.height];
+    for &(x, y) in &pattern.cells {
+        grid[y][x] = 1;
+    }
+    for &(x, y, state) in &pattern.dying {
+        grid[y][x] = state;
+    }
+    let tag = |state: u8| match (multi_state, state) {
+        (false, 0) => "b".to_string(),
+        (false, _) => "o".to_string(),
+        (true, 0) => ".".to_string(),
+        (true, state) => {
+            let (prefix, letter) = (
+                (state as usize - 1) / STATES_PER_PREFIX,
+                (state as usize - 1) % STATES_PER_PREFIX,
+            );
+            let letter = (b'A' + letter as u8) as char;
+            match prefix {
+                0 => letter.to_string(),
+                prefix => format!("{}{}", (b'p' + prefix as u8 - 1) as char, letter),
+            }
+        }
+    };
+    let token = |count: usize, tag: &str| {
+        if count == 1 {
+            tag.to_string()
+        } else {
+            format!("{}{}", count, tag)
+        }
+    };
+    let mut tokens = Vec::new();
+    let mut last_row = 0;
+    for (y, row) in grid.iter().enumerate() {
+        // 行末の死んだセルは書かない
+        let len = match row.iter().rposition(|&state| state != 0) {
+            Some(i) => i + 1,
+            None => continue,
+        };
+        if y > last_row {
+            tokens.push(token(y - last_row, "$"));
+        }
+        last_row = y;
+        let mut x = 0;
+        while x < len {
+            let run = row[x..len]
+                .iter()
+                .take_while(|&&state| state == row[x])
+                .count();
+            tokens.push(token(run, &tag(row[x])));
+            x += run;
+        }
+    }
+    tokens.push("!".to_string());
+
+    let mut line = String::new();
+    for token in tokens {
+        if line.len() + token.len() > LINE_LENGTH {
+            out += &line;
+            out += "\n";
+            line.clear();
+        }
+        line += &token;
+    }
+    out += &line;
+    out += "\n";
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_multi_state_cells() {
+        let pattern = parse("x = 5, y = 2, rule = 345/2/4\n.A2B$CpAyO!").unwrap();
+        assert_eq!(pattern.cells, vec![(1, 0)]);
+        assert_eq!(
+            pattern.dying,
+            vec![(2, 0, 2), (3, 0, 2), (0, 1, 3), (1, 1, 25), (2, 1, 255)]
+        );
+        assert_eq!((pattern.width, pattern.height), (5, 2));
+        assert!(parse("x = 1, y = 1\npo!").is_err());
+        assert!(parse("x = 1, y = 1\nyP!").is_err());
+    }
+
+    #[test]
+    fn round_trips_generations_patterns() {
+        let text = "x = 6, y = 3, rule = 345/2/4\n.A2B$2.C$pApX!\n";
+        let pattern = parse(text).unwrap();
+        assert_eq!(write(&pattern), text);
+        assert_eq!(parse(&write(&pattern)).unwrap(), pattern);
+    }
+
+    #[test]
+    fn writes_two_state_patterns_with_b_and_o() {
+        let text = "x = 3, y = 3, rule = B3/S23\nbo$2bo$3o!\n";
+        assert_eq!(write(&parse(text).unwrap()), text);
+    }
+}
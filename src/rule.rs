@@ -0,0 +1,884 @@
+use std::{fmt, ops::RangeInclusive, str::FromStr};
+
+/// セルの状態数の上限
+const MAX_STATES: usize = 256;
+/// Larger than Life のルールで数える範囲の半径の上限
+/// 1世代で変化が届く距離がタイルの一辺を超えないように、tiles::TILE_SIZE より小さくしています。
+const MAX_RANGE: usize = 10;
+
+/// 3x3 の範囲の並びの番号のうち、中央のセルのビット
+pub const CENTER: u16 = 1 << 4;
+/// 3x3 の範囲の並びの番号のうち、周りの8セルのビット
+const OUTER: u16 = 0x1ff & !CENTER;
+
+/// 周りの8セルの位置 (dx, dy) です。
+pub const NEIGHBOURS: [(isize, isize); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+/// 近傍数 1〜4 の並びに付ける Hensel 記法の文字と、その代表の並びの番号です。
+/// 回転・鏡映で重なる並びには同じ文字が付きます。近傍数 5〜7 は、8 から引いた近傍数の並びを反転したものです。
+const HENSEL_LETTERS: [&[(char, u16)]; 5] = [
+    &[],
+    &[('c', 256), ('e', 128)],
+    &[
+        ('c', 320),
+        ('e', 160),
+        ('a', 384),
+        ('i', 40),
+        ('k', 264),
+        ('n', 68),
+    ],
+    &[
+        ('c', 324),
+        ('e', 168),
+        ('a', 416),
+        ('i', 448),
+        ('k', 140),
+        ('n', 352),
+        ('j', 224),
+        ('q', 196),
+        ('r', 296),
+        ('y', 268),
+    ],
+    &[
+        ('c', 325),
+        ('e', 170),
+        ('a', 480),
+        ('i', 360),
+        ('k', 396),
+        ('n', 452),
+        ('j', 172),
+        ('q', 204),
+        ('r', 424),
+        ('y', 332),
+        ('t', 300),
+        ('w', 228),
+        ('z', 108),
+    ],
+];
+
+/// 名前で指定できるルールです。
+const NAMED_RULES: [(&str, &str); 1] = [("tlife", "B3/S2-i34q")];
+
+/// MAP 記法で使う base64 の文字
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+/// MAP 記法の文字数(512 ビットを 6 ビットずつ書いたもの)
+const MAP_LENGTH: usize = 86;
+
+/// 3x3 の範囲のうち、周りとして数えるセルの形です。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Neighbourhood {
+    /// 周りの8セル
+    Moore,
+    /// 上下左右の4セル (記法の末尾は V)
+    VonNeumann,
+    /// 奇数行を半セル右にずらして六角形に並べたときの6セル (記法の末尾は H)
+    /// 偶数行では右上と右下、奇数行では左上と左下のセルを数えません。
+    Hexagonal,
+}
+impl Neighbourhood {
+    /// 周りとして数えるセルの数を返します。
+    fn size(self) -> usize {
+        match self {
+            Neighbourhood::Moore => 8,
+            Neighbourhood::VonNeumann => 4,
+            Neighbourhood::Hexagonal => 6,
+        }
+    }
+    /// ルールの記法の末尾に付ける文字を返します。
+    fn suffix(self) -> &'static str {
+        match self {
+            Neighbourhood::Moore => "",
+            Neighbourhood::VonNeumann => "V",
+            Neighbourhood::Hexagonal => "H",
+        }
+    }
+}
+
+/// Larger than Life のルールで、生存セルを数える範囲の形です。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shape {
+    /// 正方形 (NM)
+    Moore,
+    /// ひし形 (NN)
+    VonNeumann,
+    /// 円形 (NC)。中央からの距離が半径 + 0.5 より小さいセル
+    Circular,
+}
+
+/// Larger than Life のルールです。
+/// 半径 range の範囲にある生存セルの数が birth の範囲なら誕生し、survival の範囲なら生き残ります。
+#[derive(Debug, Clone, PartialEq)]
+pub struct LargerThanLife {
+    pub range: usize,
+    /// 中央のセル自身も数えるか
+    pub middle: bool,
+    pub survival: RangeInclusive<usize>,
+    pub birth: RangeInclusive<usize>,
+    pub shape: Shape,
+}
+impl LargerThanLife {
+    /// 中央から dy 行離れた行で、数える範囲の左右それぞれの幅を返します。
+    pub fn row_width(&self, dy: usize) -> usize {
+        let r = self.range;
+        match self.shape {
+            Shape::Moore => r,
+            Shape::VonNeumann => r - dy,
+            Shape::Circular => (0..=r)
+                .rev()
+                .find(|&dx| dx * dx + dy * dy <= r * r + r)
+                .unwrap_or(0),
+        }
+    }
+}
+
+/// 中央のセルとその周りの 3x3 の範囲の並び(neighbourhood index)ごとに、
+/// 中央のセルが次の世代で生きているかを保持するルールを表します。
+/// どの並びも自由に決められ、512 ビットを base64 で書いた "MAP..." の記法で書けます。
+/// 回転・鏡映で重なる並びの扱いが同じもの(isotropic)は、"B2n3/S23-q" のような Hensel 記法で書けます。
+/// 並びを区別せず生存セルの数だけで決まるものが、外側総和型(outer-totalistic)のルールです。
+///
+/// 状態数が3以上のものは Generations 系のルールです。状態 0 が死、1 が生で、
+/// 生き残れなかった生存セルは 2, 3, ... と消えかけの状態を1世代ずつ進み、最後の状態の次に死にます。
+/// 周囲の数には生存セル(状態 1)だけを数え、誕生は死んだセル(状態 0)にだけ起こります。
+///
+/// "B2/S34H" や "B2/S013V" のように末尾に H か V を付けると、周りとして数えるセルを
+/// 六角形の6セルや上下左右の4セルに絞ります。
+///
+/// 3x3 より広い範囲の生存セルの数で決まる Larger than Life のルールは、"R5,C0,M1,S34..58,B34..45,NM" の記法で書きます。
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    /// 並びの番号ごとに、中央のセルが次の世代で生きているか
+    table: [bool; 512],
+    states: usize,
+    /// 3x3 の範囲のうち、周りとして数えるセル
+    neighbourhood: Neighbourhood,
+    /// Larger than Life のルールなら、その範囲と条件(3x3 の表は使わない)
+    larger_than_life: Option<LargerThanLife>,
+}
+impl Rule {
+    /// コンウェイのライフゲーム(B3/S23)を返します。
+    pub fn conway() -> Self {
+        let mut table = [false; 512];
+        for (index, next) in table.iter_mut().enumerate() {
+            let count = (index as u16 & OUTER).count_ones();
+            *next = count == 3 || count == 2 && index as u16 & CENTER != 0;
+        }
+        Rule {
+            table,
+            states: 2,
+            neighbourhood: Neighbourhood::Moore,
+            larger_than_life: None,
+        }
+    }
+    /// 3x3 の範囲の並びの番号から、中央のセルが次の世代で生きているかを返します。
+    pub fn next_state(&self, neighbourhood: u16) -> bool {
+        self.table[neighbourhood as usize]
+    }
+    /// 周りに生存セルがなくても死んだセルが生まれる (B0 を含む) ルールかどうかを返します。
+    pub fn has_b0(&self) -> bool {
+        self.next_state(0)
+    }
+    /// 現在の状態と 3x3 の範囲の並びの番号から、次の世代の状態を返します。
+    /// 中央のビットは見ずに、状態から決めます。
+    /// Larger than Life のルールでは、neighbourhood は中央のセルを含めた範囲内の生存セルの数です。
+    pub fn next(&self, state: u8, neighbourhood: u16) -> u8 {
+        let (birth, survival) = match &self.larger_than_life {
+            Some(rule) => {
+                let count = neighbourhood as usize - (state == 1 && !rule.middle) as usize;
+                (rule.birth.contains(&count), rule.survival.contains(&count))
+            }
+            None => {
+                let outer = neighbourhood & OUTER;
+                (
+                    self.table[outer as usize],
+                    self.table[(outer | CENTER) as usize],
+                )
+            }
+        };
+        match state {
+            0 => birth as u8,
+            1 if survival => 1,
+            _ if state as usize + 1 < self.states => state + 1,
+            _ => 0,
+        }
+    }
+    /// セルの状態数を返します。生と死だけのルールなら 2 です。
+    pub fn states(&self) -> usize {
+        self.states
+    }
+    /// 1セルの状態を詰めるのに使うビット数(1, 2, 4, 8 のいずれか)を返します。
+    pub fn state_bits(&self) -> usize {
+        [1, 2, 4, 8]
+            .into_iter()
+            .find(|&bits| self.states <= 1 << bits)
+            .unwrap_or(8)
+    }
+    /// Larger than Life のルールなら、その範囲と条件を返します。
+    pub fn larger_than_life(&self) -> Option<&LargerThanLife> {
+        self.larger_than_life.as_ref()
+    }
+    /// 周りとして数えるセルの形を返します。
+    pub fn neighbourhood(&self) -> Neighbourhood {
+        self.neighbourhood
+    }
+    /// y 行目のセルについて、3x3 の範囲の並びの番号のうち数えるセル(と中央のセル)のビットを返します。
+    /// 並びの番号はこれと論理積をとってから next に渡します。
+    pub fn mask(&self, y: usize) -> u16 {
+        match self.neighbourhood {
+            Neighbourhood::Moore => 0x1ff,
+            Neighbourhood::VonNeumann => CENTER | bit(0, -1) | bit(-1, 0) | bit(1, 0) | bit(0, 1),
+            Neighbourhood::Hexagonal if y.is_multiple_of(2) => 0x1ff & !(bit(1, -1) | bit(1, 1)),
+            Neighbourhood::Hexagonal => 0x1ff & !(bit(-1, -1) | bit(-1, 1)),
+        }
+    }
+    /// 生と死の2状態で、周りの8セルで決まるルールかどうかを返します。
+    /// dense 以外の計算方法やセンサスは、このルールにしか使えません。
+    pub fn is_life_like(&self) -> bool {
+        self.states == 2
+            && self.neighbourhood == Neighbourhood::Moore
+            && self.larger_than_life.is_none()
+    }
+    /// 生存セルの数だけで決まる(並びによらない)ルールかどうかを返します。
+    pub fn is_totalistic(&self) -> bool {
+        (0..512u16).all(|index| {
+            let first = first_neighbours((index & OUTER).count_ones() as usize) | index & CENTER;
+            self.table[index as usize] == self.table[first as usize]
+        })
+    }
+    /// 回転・鏡映で重なる並びの扱いが同じルールかどうかを返します。
+    fn is_isotropic(&self) -> bool {
+        (0..512u16).all(|index| {
+            symmetries(index)
+                .iter()
+                .all(|&image| self.table[image as usize] == self.table[index as usize])
+        })
+    }
+}
+
+/// 3x3 の範囲の (dx, dy) のセルに対応する、並びの番号のビットを返します。
+/// 左上から行ごとに読んだ順に上位のビットから割り当てるため、番号の順は MAP 記法と同じになります。
+pub fn bit(dx: isize, dy: isize) -> u16 {
+    1 << (8 - (dy + 1) * 3 - (dx + 1))
+}
+
+/// 3x3 の範囲のセルが生きているか(中央からの dx, dy を受け取る is_live)から、並びの番号を作ります。
+pub fn neighbourhood(is_live: impl Fn(isize, isize) -> bool) -> u16 {
+    let mut index = 0;
+    for dy in -1..=1 {
+        for dx in -1..=1 {
+            if is_live(dx, dy) {
+                index |= bit(dx, dy);
+            }
+        }
+    }
+    index
+}
+
+/// 周りに生存セルが count 個ある並びの1つ(NEIGHBOURS の先頭から count 個が生きているもの)の番号を返します。
+pub fn first_neighbours(count: usize) -> u16 {
+    NEIGHBOURS[..count]
+        .iter()
+        .fold(0, |index, &(dx, dy)| index | bit(dx, dy))
+}
+
+/// 並びを回転・鏡映した8通りの並びの番号を返します。
+fn symmetries(index: u16) -> [u16; 8] {
+    let mut images = [0; 8];
+    for (t, image) in images.iter_mut().enumerate() {
+        *image = neighbourhood(|dx, dy| {
+            // (dx, dy) に写るセルの元の位置を求める
+            let (dx, dy) = (
+                if t & 1 != 0 { -dx } else { dx },
+                if t & 2 != 0 { -dy } else { dy },
+            );
+            let (dx, dy) = if t & 4 != 0 { (dy, dx) } else { (dx, dy) };
+            index & bit(dx, dy) != 0
+        });
+    }
+    images
+}
+
+/// 近傍数 count の並びに付ける (文字, 代表の並びの番号) を、記法での順に返します。
+fn letters(count: usize) -> Vec<(char, u16)> {
+    match count {
+        0..=4 => HENSEL_LETTERS[count].to_vec(),
+        _ => HENSEL_LETTERS[8 - count]
+            .iter()
+            .map(|&(letter, index)| (letter, OUTER ^ index))
+            .collect(),
+    }
+}
+
+/// 並びに付く Hensel 記法の文字を返します。中央のセルは見ません。近傍数が 0 か 8 なら None です。
+fn letter(index: u16) -> Option<char> {
+    let outer = index & OUTER;
+    letters(outer.count_ones() as usize)
+        .into_iter()
+        .find(|&(_, representative)| symmetries(representative).contains(&outer))
+        .map(|(letter, _)| letter)
+}
+
+/// 中央のビットが center の並びについて、表を Hensel 記法で書きます。
+/// 近傍数ごとに、すべての並びなら数字だけ、半分以下なら含む文字を、それより多ければ '-' の後に含まない文字を書きます。
+fn hensel(table: &[bool; 512], center: u16) -> String {
+    let mut text = String::new();
+    for count in 0..=8 {
+        let letters = letters(count);
+        let digit = char::from(b'0' + count as u8);
+        if letters.is_empty() {
+            if table[(first_neighbours(count) | center) as usize] {
+                text.push(digit);
+            }
+            continue;
+        }
+        let (present, absent): (Vec<_>, Vec<_>) = letters
+            .iter()
+            .partition(|&&(_, index)| table[(index | center) as usize]);
+        if present.is_empty() {
+            continue;
+        }
+        text.push(digit);
+        if absent.is_empty() {
+            continue;
+        }
+        if present.len() * 2 <= letters.len() {
+            text.extend(present.iter().map(|&(letter, _)| letter));
+        } else {
+            text.push('-');
+            text.extend(absent.iter().map(|&(letter, _)| letter));
+        }
+    }
+    text
+}
+
+/// 表を MAP 記法の base64 の部分(パディングなし)で書きます。
+fn encode_map(table: &[bool; 512]) -> String {
+    table
+        .chunks(6)
+        .map(|bits| {
+            let value = bits
+                .iter()
+                .enumerate()
+                .fold(0, |value, (i, &bit)| value | (bit as usize) << (5 - i));
+            char::from(BASE64[value])
+        })
+        .collect()
+}
+
+impl Default for Rule {
+    fn default() -> Self {
+        Rule::conway()
+    }
+}
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(rule) = &self.larger_than_life {
+            let states = if self.states == 2 { 0 } else { self.states };
+            let shape = match rule.shape {
+                Shape::Moore => 'M',
+                Shape::VonNeumann => 'N',
+                Shape::Circular => 'C',
+            };
+            return write!(
+                f,
+                "R{},C{},M{},S{}..{},B{}..{},N{}",
+                rule.range,
+                states,
+                rule.middle as u8,
+                rule.survival.start(),
+                rule.survival.end(),
+                rule.birth.start(),
+                rule.birth.end(),
+                shape
+            );
+        }
+        if !self.is_isotropic() {
+            // 並びを区別するルールは MAP 記法でしか書けない(状態数は常に 2)
+            return write!(f, "MAP{}", encode_map(&self.table));
+        }
+        let (birth, survival) = (hensel(&self.table, 0), hensel(&self.table, CENTER));
+        let suffix = self.neighbourhood.suffix();
+        if self.states == 2 {
+            write!(f, "B{}/S{}{}", birth, survival, suffix)
+        } else {
+            // Generations 系は 生存/誕生/状態数 の順で書く
+            write!(f, "{}/{}/{}{}", survival, birth, self.states, suffix)
+        }
+    }
+}
+
+/// ルール文字列の解析に失敗した理由です。
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuleError {
+    /// 空文字列が渡された
+    Empty,
+    /// "B/S" の区切りが1つではない
+    Separator,
+    /// B/S の接頭辞が付いている部分と付いていない部分が混在している
+    MixedNotation,
+    /// 同じ部分(B か S)が2回指定された
+    Duplicate(char),
+    /// 0〜8 以外の文字が含まれている
+    InvalidDigit(char),
+    /// 近傍数 count の並びにない Hensel 記法の文字が含まれている
+    InvalidLetter { count: usize, letter: char },
+    /// 状態数が 2〜256 の整数ではない
+    InvalidStates(String),
+    /// MAP 記法の base64 の部分が正しくない
+    InvalidMap,
+    /// Larger than Life の記法の項目が正しくないか、足りない
+    InvalidItem(String),
+}
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuleError::Empty => write!(f, "ルール文字列が空です"),
+            RuleError::Separator => {
+                write!(
+                    f,
+                    "ルール文字列は '/' で2つ(Generations 系は3つ)に区切ってください"
+                )
+            }
+            RuleError::MixedNotation => {
+                write!(f, "B/S 表記と S/B 表記が混在しています")
+            }
+            RuleError::Duplicate(c) => write!(f, "'{}' が2回指定されています", c),
+            RuleError::InvalidDigit(c) => write!(f, "不正な近傍数です: '{}'", c),
+            RuleError::InvalidLetter { count, letter } => {
+                write!(f, "近傍数 {} の並びに '{}' はありません", count, letter)
+            }
+            RuleError::InvalidStates(s) => {
+                write!(
+                    f,
+                    "状態数は 2〜{} の整数で指定してください: '{}'",
+                    MAX_STATES, s
+                )
+            }
+            RuleError::InvalidMap => write!(
+                f,
+                "MAP 記法は 512 ビットを base64 で書いた {} 文字にしてください",
+                MAP_LENGTH
+            ),
+            RuleError::InvalidItem(item) => write!(
+                f,
+                "Larger than Life の記法の項目が正しくないか、足りません: '{}'",
+                item
+            ),
+        }
+    }
+}
+impl std::error::Error for RuleError {}
+
+impl FromStr for Rule {
+    type Err = RuleError;
+    /// "B3/S23" 形式と、接頭辞なしの "23/3"(生存/誕生)形式を受け付けます。
+    /// Generations 系のルールは、3つ目に状態数を付けた "345/2/4"(生存/誕生/状態数)や
+    /// "B2/S/C3" の形で書きます。
+    /// 近傍数の後には "B2n3/S23-q" のように Hensel 記法の文字を付けられ、"tlife" のような名前も使えます。
+    /// 3x3 の範囲の512通りの並びそれぞれの結果を決めるルールは、"MAP" に続けて base64 で書きます。
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(RuleError::Empty);
+        }
+        if let Some(code) = s.strip_prefix("MAP") {
+            return parse_map(code);
+        }
+        if s.contains(',') {
+            return parse_larger_than_life(s);
+        }
+        if let Some((_, rule)) = NAMED_RULES
+            .iter()
+            .find(|(name, _)| s.eq_ignore_ascii_case(name))
+        {
+            return rule.parse();
+        }
+        let (s, neighbourhood) = match s.chars().last().map(|c| c.to_ascii_uppercase()) {
+            Some('V') => (&s[..s.len() - 1], Neighbourhood::VonNeumann),
+            Some('H') => (&s[..s.len() - 1], Neighbourhood::Hexagonal),
+            _ => (s, Neighbourhood::Moore),
+        };
+        let mut parts: Vec<&str> = s.split('/').collect();
+        let states = match parts.len() {
+            2 => 2,
+            3 => parse_states(parts.pop().unwrap_or_default())?,
+            _ => return Err(RuleError::Separator),
+        };
+        let prefix = |part: &str| part.chars().next().map(|c| c.to_ascii_uppercase());
+        let (birth, survival) = match (prefix(parts[0]), prefix(parts[1])) {
+            (Some(a @ ('B' | 'S')), Some(b @ ('B' | 'S'))) => {
+                if a == b {
+                    return Err(RuleError::Duplicate(a));
+                }
+                if a == 'B' {
+                    (&parts[0][1..], &parts[1][1..])
+                } else {
+                    (&parts[1][1..], &parts[0][1..])
+                }
+            }
+            (Some('B' | 'S'), _) | (_, Some('B' | 'S')) => return Err(RuleError::MixedNotation),
+            // 接頭辞がない場合は 生存/誕生 の順で書かれている
+            _ => (parts[1], parts[0]),
+        };
+        let mut table = [false; 512];
+        parse_transitions(birth, 0, neighbourhood, &mut table)?;
+        parse_transitions(survival, CENTER, neighbourhood, &mut table)?;
+        Ok(Rule {
+            table,
+            states,
+            neighbourhood,
+            larger_than_life: None,
+        })
+    }
+}
+
+/// "23-q" のような近傍数と Hensel 記法の文字の並びを解析し、
+/// 中央のビットが center の並びのうち選ばれたものを、表で生きている結果にします。
+/// 周りの形が Moore 以外なら、近傍数はその形のセルの数までで、文字は付けられません。
+/// 数えないセルは mask で 0 にしてから表を引くので、表は周りの8セルの数だけで決めておけば足ります。
+fn parse_transitions(
+    part: &str,
+    center: u16,
+    neighbourhood: Neighbourhood,
+    table: &mut [bool; 512],
+) -> Result<(), RuleError> {
+    let mut chars = part.chars().peekable();
+    while let Some(c) = chars.next() {
+        let count = match c.to_digit(10) {
+            Some(n) if n as usize <= neighbourhood.size() => n as usize,
+            _ => return Err(RuleError::InvalidDigit(c)),
+        };
+        let negated = chars.next_if_eq(&'-').is_some();
+        let mut selected = Vec::new();
+        while let Some(letter) = chars.next_if(char::is_ascii_lowercase) {
+            if neighbourhood != Neighbourhood::Moore
+                || !letters(count).iter().any(|&(l, _)| l == letter)
+            {
+                return Err(RuleError::InvalidLetter { count, letter });
+            }
+            selected.push(letter);
+        }
+        for outer in (0..512u16).filter(|&i| i & CENTER == 0 && i.count_ones() as usize == count) {
+            // 文字がなければ、その近傍数のすべての並びを選ぶ
+            let listed = letter(outer).is_some_and(|l| selected.contains(&l));
+            if selected.is_empty() || listed != negated {
+                table[(outer | center) as usize] = true;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// MAP 記法の base64 の部分を解析します。末尾の '=' のパディングは付いていてもいなくても構いません。
+fn parse_map(code: &str) -> Result<Rule, RuleError> {
+    let code = code.trim_end_matches('=');
+    if code.len() != MAP_LENGTH {
+        return Err(RuleError::InvalidMap);
+    }
+    let mut table = [false; 512];
+    for (i, c) in code.bytes().enumerate() {
+        let value = BASE64
+            .iter()
+            .position(|&b| b == c)
+            .ok_or(RuleError::InvalidMap)?;
+        for bit in 0..6 {
+            // 最後の文字の余りのビットは使わない
+            if let Some(next) = table.get_mut(i * 6 + bit) {
+                *next = value >> (5 - bit) & 1 == 1;
+            }
+        }
+    }
+    Ok(Rule {
+        table,
+        states: 2,
+        neighbourhood: Neighbourhood::Moore,
+        larger_than_life: None,
+    })
+}
+
+/// "R5,C0,M1,S34..58,B34..45,NM" の形の Larger than Life のルールを解析します。
+/// C は状態数で、0 は 2 と同じです。
+fn parse_larger_than_life(s: &str) -> Result<Rule, RuleError> {
+    let mut items = s.split(',').map(str::trim);
+    let mut next = |key: char| {
+        items
+            .next()
+            .and_then(|item| item.strip_prefix([key, key.to_ascii_lowercase()]))
+            .ok_or_else(|| RuleError::InvalidItem(key.to_string()))
+    };
+    let invalid = |item: &str| RuleError::InvalidItem(item.to_string());
+    let number = |value: &str| value.parse::<usize>().map_err(|_| invalid(value));
+    let counts = |value: &str| -> Result<RangeInclusive<usize>, RuleError> {
+        let (min, max) = value.split_once("..").ok_or_else(|| invalid(value))?;
+        Ok(number(min)?..=number(max)?)
+    };
+    let value = next('R')?;
+    let range = match number(value)? {
+        range @ 1..=MAX_RANGE => range,
+        _ => return Err(invalid(value)),
+    };
+    let value = next('C')?;
+    let states = match number(value)? {
+        states @ 0..=MAX_STATES => states.max(2),
+        _ => return Err(RuleError::InvalidStates(value.to_string())),
+    };
+    let middle = match next('M')? {
+        "0" => false,
+        "1" => true,
+        value => return Err(invalid(value)),
+    };
+    let survival = counts(next('S')?)?;
+    let birth = counts(next('B')?)?;
+    let shape = match next('N')? {
+        "M" | "m" => Shape::Moore,
+        "N" | "n" => Shape::VonNeumann,
+        "C" | "c" => Shape::Circular,
+        value => return Err(invalid(value)),
+    };
+    if let Some(item) = items.next() {
+        return Err(invalid(item));
+    }
+    Ok(Rule {
+        table: [false; 512],
+        states,
+        neighbourhood: Neighbourhood::Moore,
+        larger_than_life: Some(LargerThanLife {
+            range,
+            middle,
+            survival,
+            birth,
+            shape,
+        }),
+    })
+}
+
+/// Generations 系のルールの状態数を解析します。"C" の接頭辞は付いていてもいなくても構いません。
+fn parse_states(part: &str) -> Result<usize, RuleError> {
+    let digits = part.strip_prefix(['C', 'c']).unwrap_or(part);
+    match digits.parse() {
+        Ok(states @ 2..=MAX_STATES) => Ok(states),
+        _ => Err(RuleError::InvalidStates(part.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// B3/S23 を MAP 記法で書いたもの
+    const CONWAY_MAP: &str =
+        "MAPARYXfhZofugWaH7oaIDogBZofuhogOiAaIDogIAAgAAWaH7oaIDogGiA6ICAAIAAaIDogIAAgACAAIAAAAAAAA";
+
+    #[test]
+    fn map_matches_conway() {
+        let rule: Rule = CONWAY_MAP.parse().unwrap();
+        assert_eq!(rule, Rule::conway());
+        assert_eq!(rule.to_string(), "B3/S23");
+        assert_eq!(format!("{}==", CONWAY_MAP).parse::<Rule>().unwrap(), rule);
+        assert_eq!(
+            format!("MAP{}", encode_map(&Rule::conway().table)),
+            CONWAY_MAP
+        );
+        // 回転・鏡映で重なる並びを区別するルールは MAP 記法のまま書く
+        let mut table = Rule::conway().table;
+        table[bit(-1, -1) as usize] = true;
+        let text = format!("MAP{}", encode_map(&table));
+        let rule: Rule = text.parse().unwrap();
+        assert_eq!(rule.to_string(), text);
+    }
+
+    #[test]
+    fn rejects_broken_maps() {
+        assert_eq!("MAPabc".parse::<Rule>(), Err(RuleError::InvalidMap));
+        let broken = format!("{}!", &CONWAY_MAP[..CONWAY_MAP.len() - 1]);
+        assert_eq!(broken.parse::<Rule>(), Err(RuleError::InvalidMap));
+    }
+
+    #[test]
+    fn larger_than_life_round_trips() {
+        for (text, expected) in [
+            ("R5,C0,M1,S34..58,B34..45,NM", "R5,C0,M1,S34..58,B34..45,NM"),
+            ("r2,c3,m0,s1..3,b2..2,nc", "R2,C3,M0,S1..3,B2..2,NC"),
+            ("R1,C2,M0,S2..3,B3..3,NN", "R1,C0,M0,S2..3,B3..3,NN"),
+        ] {
+            let rule: Rule = text.parse().unwrap();
+            assert_eq!(rule.to_string(), expected, "{}", text);
+            assert_eq!(expected.parse::<Rule>().unwrap(), rule, "{}", text);
+        }
+        let rule: Rule = "R2,C3,M0,S1..3,B2..2,NC".parse().unwrap();
+        assert_eq!(rule.states(), 3);
+        let ltl = rule.larger_than_life().unwrap();
+        assert_eq!(
+            (ltl.range, ltl.middle, ltl.shape),
+            (2, false, Shape::Circular)
+        );
+    }
+
+    #[test]
+    fn rejects_broken_larger_than_life_rules() {
+        for (text, item) in [
+            ("R5,C0,M1,S34..58", "B"),
+            ("R0,C0,M1,S1..2,B1..2,NM", "0"),
+            ("R11,C0,M1,S1..2,B1..2,NM", "11"),
+            ("R1,C0,M2,S1..2,B1..2,NM", "2"),
+            ("R1,C0,M1,S1-2,B1..2,NM", "1-2"),
+            ("R1,C0,M1,S1..2,B1..2,NX", "X"),
+            ("R1,C0,M1,S1..2,B1..2,NM,X", "X"),
+        ] {
+            assert_eq!(
+                text.parse::<Rule>(),
+                Err(RuleError::InvalidItem(item.to_string())),
+                "{}",
+                text
+            );
+        }
+        assert_eq!(
+            "R1,C300,M1,S1..2,B1..2,NM".parse::<Rule>(),
+            Err(RuleError::InvalidStates("300".to_string()))
+        );
+    }
+
+    #[test]
+    fn neighbourhood_suffixes_round_trip() {
+        for (text, expected, neighbourhood) in [
+            ("B2/S34H", "B2/S34H", Neighbourhood::Hexagonal),
+            ("b2/s34h", "B2/S34H", Neighbourhood::Hexagonal),
+            ("B2/S013V", "B2/S013V", Neighbourhood::VonNeumann),
+            ("34/2/3H", "34/2/3H", Neighbourhood::Hexagonal),
+            ("B3/S23", "B3/S23", Neighbourhood::Moore),
+        ] {
+            let rule: Rule = text.parse().unwrap();
+            assert_eq!(rule.to_string(), expected, "{}", text);
+            assert_eq!(rule.neighbourhood(), neighbourhood, "{}", text);
+            assert_eq!(expected.parse::<Rule>().unwrap(), rule, "{}", text);
+        }
+        assert_eq!(
+            "B2a/S2H".parse::<Rule>(),
+            Err(RuleError::InvalidLetter {
+                count: 2,
+                letter: 'a'
+            })
+        );
+        assert_eq!("B5/S1V".parse::<Rule>(), Err(RuleError::InvalidDigit('5')));
+        assert_eq!("B7/S1H".parse::<Rule>(), Err(RuleError::InvalidDigit('7')));
+    }
+
+    #[test]
+    fn masks_count_only_the_neighbourhood() {
+        let count = |rule: &Rule, y: usize| (rule.mask(y) & !CENTER).count_ones();
+        let hexagonal: Rule = "B2/S34H".parse().unwrap();
+        assert_eq!((count(&hexagonal, 0), count(&hexagonal, 1)), (6, 6));
+        // 偶数行では右上と右下、奇数行では左上と左下を数えない
+        assert_eq!(hexagonal.mask(0) & (bit(1, -1) | bit(1, 1)), 0);
+        assert_eq!(hexagonal.mask(1) & (bit(-1, -1) | bit(-1, 1)), 0);
+        let von_neumann: Rule = "B2/S013V".parse().unwrap();
+        assert_eq!(count(&von_neumann, 0), 4);
+        assert_eq!(von_neumann.mask(0) & bit(1, 1), 0);
+        assert_eq!(Rule::conway().mask(0), 0x1ff);
+        // 2 セルだけが数える位置にあれば B2 で生まれる
+        let two = bit(-1, 0) | bit(1, 0);
+        assert_eq!(hexagonal.next(0, two & hexagonal.mask(0)), 1);
+        assert_eq!(
+            von_neumann.next(0, (two | bit(1, 1)) & von_neumann.mask(0)),
+            1
+        );
+    }
+
+    #[test]
+    fn hensel_letters_split_each_count_into_classes() {
+        // 回転・鏡映で重なる並びのまとまりの数(近傍数 0〜8)
+        let classes = [1, 2, 6, 10, 13, 10, 6, 2, 1];
+        for (count, &expected) in classes.iter().enumerate() {
+            let outers: Vec<u16> = (0..512u16)
+                .filter(|&i| i & CENTER == 0 && i.count_ones() as usize == count)
+                .collect();
+            let mut found: Vec<char> = outers.iter().filter_map(|&i| letter(i)).collect();
+            found.sort_unstable();
+            found.dedup();
+            match count {
+                0 | 8 => assert!(found.is_empty()),
+                _ => assert_eq!(found.len(), expected, "近傍数 {}", count),
+            }
+            for &outer in &outers {
+                // 同じまとまりの並びには同じ文字が付き、中央のセルは文字に関係しない
+                for image in symmetries(outer) {
+                    assert_eq!(letter(image), letter(outer));
+                }
+                assert_eq!(letter(outer | CENTER), letter(outer));
+                // 近傍数 5〜7 の文字は、反転した近傍数 1〜3 の並びの文字と同じ
+                if count != 4 {
+                    assert_eq!(letter(OUTER ^ outer), letter(outer));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for (text, expected) in [
+            ("B3/S23", "B3/S23"),
+            ("23/3", "B3/S23"),
+            ("S23/B3", "B3/S23"),
+            ("B2n3/S23-q", "B2n3/S23-q"),
+            ("B2-a/S12", "B2-a/S12"),
+            ("tlife", "B3/S2-i34q"),
+            ("/2/3", "/2/3"),
+            ("B2/S/C3", "/2/3"),
+            ("345/2/4", "345/2/4"),
+        ] {
+            let rule: Rule = text.parse().unwrap();
+            assert_eq!(rule.to_string(), expected, "{}", text);
+            assert_eq!(expected.parse::<Rule>().unwrap(), rule, "{}", text);
+        }
+    }
+
+    #[test]
+    fn hensel_letters_select_neighbourhoods() {
+        let rule: Rule = "B2n3/S23-q".parse().unwrap();
+        // 2n は向かい合った角の2セル、3q は 3 のうち q だけを除く
+        assert!(rule.next_state(bit(1, -1) | bit(-1, 1)));
+        assert!(!rule.next_state(bit(-1, -1) | bit(1, -1)));
+        let three_q = HENSEL_LETTERS[3]
+            .iter()
+            .find(|&&(l, _)| l == 'q')
+            .unwrap()
+            .1;
+        assert!(rule.next_state(three_q));
+        assert!(!rule.next_state(three_q | CENTER));
+        assert!(rule.next_state(first_neighbours(3) | CENTER));
+        assert!(!rule.is_totalistic());
+    }
+
+    #[test]
+    fn reports_errors() {
+        for (text, error) in [
+            ("", RuleError::Empty),
+            ("B3", RuleError::Separator),
+            ("B3/S23/4/5", RuleError::Separator),
+            ("B3/23", RuleError::MixedNotation),
+            ("B3/B23", RuleError::Duplicate('B')),
+            ("B9/S23", RuleError::InvalidDigit('9')),
+            ("B3#/S23", RuleError::InvalidDigit('#')),
+            (
+                "B2z/S23",
+                RuleError::InvalidLetter {
+                    count: 2,
+                    letter: 'z',
+                },
+            ),
+            ("23/3/1", RuleError::InvalidStates("1".to_string())),
+            ("23/3/257", RuleError::InvalidStates("257".to_string())),
+        ] {
+            assert_eq!(text.parse::<Rule>(), Err(error), "{:?}", text);
+        }
+    }
+}
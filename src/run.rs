@@ -0,0 +1,115 @@
+use crate::{
+    cli::{Config, Output},
+    stats::StatsLog,
+    summary::{RunOutcome, RunSummary},
+    universe::Universe,
+};
+
+/// 最大世代数に達するか、全滅するか、以前の盤面に(移動して)戻るまで世代を進めます。
+/// stats があれば、最初の盤面から毎世代の統計を書き出します。
+pub fn run(
+    universe: &mut impl Universe,
+    config: &Config,
+    mut stats: Option<&mut StatsLog>,
+) -> RunSummary {
+    if config.output == Output::Ascii {
+        universe.show_board();
+    }
+    if let Some(stats) = stats.as_deref_mut() {
+        stats.record(universe);
+    }
+    let mut peak_population = universe.population();
+    // 最初から生存セルがなければ、世代を進めずに終える
+    let (outcome, cycle_start) = if universe.population() == 0 {
+        (RunOutcome::Extinct, None)
+    } else {
+        loop {
+            if let Some(max) = config.max_generations {
+                if universe.generation() >= max {
+                    break (RunOutcome::MaxGenerations, None);
+                }
+            }
+            universe.step();
+            if config.output == Output::Ascii {
+                universe.show_board();
+            }
+            if let Some(stats) = stats.as_deref_mut() {
+                stats.record(universe);
+            }
+            peak_population = peak_population.max(universe.population());
+            if let Some(finished) = check(universe) {
+                break finished;
+            }
+        }
+    };
+    if config.output == Output::Final {
+        universe.show_board();
+    }
+    summarize(universe, outcome, cycle_start, peak_population)
+}
+
+/// 全滅したか、以前の盤面に(移動して)戻っていれば、その結果と繰り返しが始まった世代を返します。
+pub fn check(universe: &impl Universe) -> Option<(RunOutcome, Option<u64>)> {
+    if universe.population() == 0 {
+        return Some((RunOutcome::Extinct, None));
+    }
+    universe.cycle().map(|cycle| {
+        let outcome = match (cycle.period, cycle.dx, cycle.dy) {
+            (1, 0, 0) => RunOutcome::StillLife,
+            (period, 0, 0) => RunOutcome::Oscillator { period },
+            (period, dx, dy) => RunOutcome::Spaceship { period, dx, dy },
+        };
+        (outcome, Some(cycle.start))
+    })
+}
+
+/// 現在の盤面から実行結果のまとめを作ります。
+pub fn summarize(
+    universe: &impl Universe,
+    outcome: RunOutcome,
+    cycle_start: Option<u64>,
+    peak_population: u64,
+) -> RunSummary {
+    RunSummary {
+        outcome,
+        cycle_start,
+        generations: universe.generation(),
+        population: universe.population(),
+        peak_population,
+        bounding_box: universe.bounding_box(),
+        seed: None,
+        census: None,
+        apgcode: None,
+        tiles: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Board;
+
+    fn config() -> Config {
+        Config {
+            output: Output::None,
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn empty_board_is_extinct_without_stepping() {
+        let mut board = Board::new(10, 10, 10);
+        let summary = run(&mut board, &config(), None);
+        assert_eq!(summary.outcome, RunOutcome::Extinct);
+        assert_eq!(summary.generations, 0);
+    }
+
+    #[test]
+    fn blinker_reports_start_of_cycle() {
+        let mut board = Board::new(10, 10, 10);
+        board.set_live([(4, 5), (5, 5), (6, 5)]);
+        let summary = run(&mut board, &config(), None);
+        assert_eq!(summary.outcome, RunOutcome::Oscillator { period: 2 });
+        assert_eq!((summary.cycle_start, summary.generations), (Some(0), 2));
+    }
+}
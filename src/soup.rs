@@ -0,0 +1,186 @@
+//! 盤面をランダムに埋める初期配置(スープ)を作ります。
+
+use crate::random::Random;
+use std::{fmt, str::FromStr};
+
+/// スープに持たせる対称性です。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Symmetry {
+    /// 対称性なし
+    C1,
+    /// 左右対称
+    D2,
+    /// 左右・上下対称
+    D4,
+    /// 左右・上下・対角線に対称(範囲は正方形になる)
+    D8,
+}
+impl FromStr for Symmetry {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "C1" => Ok(Symmetry::C1),
+            "D2" => Ok(Symmetry::D2),
+            "D4" => Ok(Symmetry::D4),
+            "D8" => Ok(Symmetry::D8),
+            _ => Err(()),
+        }
+    }
+}
+impl fmt::Display for Symmetry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Symmetry::C1 => "C1",
+            Symmetry::D2 => "D2",
+            Symmetry::D4 => "D4",
+            Symmetry::D8 => "D8",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// スープで埋める範囲です。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Region {
+    /// 盤面全体
+    Whole,
+    /// 盤面の中央に置いた width x height の範囲
+    Centered { width: usize, height: usize },
+}
+impl FromStr for Region {
+    type Err = ();
+    /// "whole" か "16x16" の形を受け付けます。
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "whole" {
+            return Ok(Region::Whole);
+        }
+        let (width, height) = s.split_once('x').ok_or(())?;
+        match (width.parse(), height.parse()) {
+            (Ok(width @ 1..), Ok(height @ 1..)) => Ok(Region::Centered { width, height }),
+            _ => Err(()),
+        }
+    }
+}
+
+/// スープの作り方です。同じ設定からは、どの環境でも同じ配置ができます。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Soup {
+    pub density: f64,
+    pub region: Region,
+    pub symmetry: Symmetry,
+    pub seed: u64,
+}
+impl Soup {
+    /// width x height の盤面に置く生存セルの座標を返します。
+    /// 大きな盤面でも座標をまとめて持たないように、1つずつ作って返します。
+    /// 対称の軸の上のセルは、同じ座標が2回以上返ることがあります。
+    pub fn cells(&self, width: usize, height: usize) -> impl Iterator<Item = (usize, usize)> {
+        let (mut w, mut h) = match self.region {
+            Region::Whole => (width, height),
+            Region::Centered {
+                width: w,
+                height: h,
+            } => (w.min(width), h.min(height)),
+        };
+        if self.symmetry == Symmetry::D8 {
+            w = w.min(h);
+            h = w;
+        }
+        let (x0, y0) = ((width - w) / 2, (height - h) / 2);
+        // 対称性で決まらない部分だけを乱数で埋め、残りは鏡映で写す
+        let (half_w, half_h) = match self.symmetry {
+            Symmetry::C1 => (w, h),
+            Symmetry::D2 => (w.div_ceil(2), h),
+            Symmetry::D4 | Symmetry::D8 => (w.div_ceil(2), h.div_ceil(2)),
+        };
+        let (symmetry, density) = (self.symmetry, self.density);
+        let mut random = Random::new(self.seed);
+        (0..half_h)
+            .flat_map(move |y| (0..half_w).map(move |x| (x, y)))
+            .filter(move |&(x, y)| !(symmetry == Symmetry::D8 && x > y))
+            .filter(move |_| random.chance(density))
+            .flat_map(move |(x, y)| {
+                let mut images = vec![(x, y)];
+                if symmetry != Symmetry::C1 {
+                    images.push((w - 1 - x, y));
+                }
+                if matches!(symmetry, Symmetry::D4 | Symmetry::D8) {
+                    images.push((x, h - 1 - y));
+                    images.push((w - 1 - x, h - 1 - y));
+                }
+                if symmetry == Symmetry::D8 {
+                    let transposed: Vec<_> = images.iter().map(|&(x, y)| (y, x)).collect();
+                    images.extend(transposed);
+                }
+                images.into_iter().map(move |(x, y)| (x0 + x, y0 + y))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    fn soup(symmetry: Symmetry, region: Region, seed: u64) -> Soup {
+        Soup {
+            density: 0.5,
+            region,
+            symmetry,
+            seed,
+        }
+    }
+
+    fn cells(soup: Soup, width: usize, height: usize) -> BTreeSet<(usize, usize)> {
+        soup.cells(width, height).collect()
+    }
+
+    #[test]
+    fn same_seed_gives_same_cells() {
+        let a = soup(Symmetry::C1, Region::Whole, 1);
+        assert_eq!(cells(a, 30, 20), cells(a, 30, 20));
+        assert_ne!(cells(a, 30, 20), cells(Soup { seed: 2, ..a }, 30, 20));
+        assert!(!cells(a, 30, 20).is_empty());
+    }
+
+    #[test]
+    fn symmetric_soups_are_symmetric() {
+        // 偶数の盤面に奇数の範囲を置くと、範囲の中央の列・行が軸になる
+        let (width, height) = (20, 20);
+        let region = Region::Centered {
+            width: 7,
+            height: 5,
+        };
+        for symmetry in [Symmetry::D2, Symmetry::D4, Symmetry::D8] {
+            for seed in 0..5 {
+                let cells = cells(soup(symmetry, region, seed), width, height);
+                let (w, h) = match symmetry {
+                    Symmetry::D8 => (5, 5),
+                    _ => (7, 5),
+                };
+                let (x0, y0) = ((width - w) / 2, (height - h) / 2);
+                for &(x, y) in &cells {
+                    assert!((x0..x0 + w).contains(&x) && (y0..y0 + h).contains(&y));
+                    let (x, y) = (x - x0, y - y0);
+                    let mut images = vec![(w - 1 - x, y)];
+                    if symmetry != Symmetry::D2 {
+                        images.push((x, h - 1 - y));
+                    }
+                    if symmetry == Symmetry::D8 {
+                        images.push((y, x));
+                    }
+                    for (ix, iy) in images {
+                        assert!(
+                            cells.contains(&(x0 + ix, y0 + iy)),
+                            "{} のシード {} で ({}, {}) の像がありません",
+                            symmetry,
+                            seed,
+                            x0 + x,
+                            y0 + y
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
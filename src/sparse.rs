@@ -0,0 +1,247 @@
+use crate::{
+    cycle::{Cycle, CycleDetector, Snapshot},
+    pattern::Pattern,
+    rule::{self, Rule},
+    summary,
+};
+use std::collections::{HashMap, HashSet};
+
+/// 生存セルの座標だけを保持する、大きさに制限のない盤面です。
+/// パターンが広がっても、生存セルの数に比例したメモリしか使いません。
+/// 死んだセルは周囲に生存セルがある場合しか評価しないため、B0 を含むルールでは正しく動作しません。
+pub struct SparseBoard {
+    live: HashSet<(i64, i64)>,
+    old_boards: CycleDetector,
+    generation: u64,
+    rule: Rule,
+    /// 直前の1世代で (生まれたセル, 死んだセル) の数
+    changes: (u64, u64),
+}
+impl SparseBoard {
+    pub fn new(board_histories: usize) -> Self {
+        SparseBoard {
+            live: HashSet::new(),
+            old_boards: CycleDetector::new(board_histories),
+            generation: 0,
+            rule: Rule::default(),
+            changes: (0, 0),
+        }
+    }
+    /// 世代交代に使うルールを設定します。
+    pub fn set_rule(&mut self, rule: Rule) {
+        self.rule = rule;
+    }
+    pub fn set_live(&mut self, points: Vec<(i64, i64)>) {
+        self.live.extend(points);
+    }
+    /// 生存セルの座標を返します。
+    pub fn live_cells(&self) -> Vec<(i64, i64)> {
+        self.live.iter().copied().collect()
+    }
+    /// 生存セルを囲む範囲をパターンとして取り出します。
+    pub fn to_pattern(&self) -> Pattern {
+        Pattern::from_points(self.live.iter().copied(), Some(self.rule.clone()))
+    }
+    /// 1世代進めます。
+    pub fn step(&mut self) {
+        let (shape, offset) = self.shape();
+        self.old_boards.record(self.generation, shape, offset);
+        self.generation += 1;
+        // 生存セルとその周囲のセルにだけ、3x3 の範囲の生存セルの並びを書き込む
+        let mut neighbourhoods: HashMap<(i64, i64), u16> = HashMap::new();
+        for &(x, y) in &self.live {
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    // (x - dx, y - dy) のセルから見ると、このセルは (dx, dy) の位置にある
+                    *neighbourhoods
+                        .entry((x - dx as i64, y - dy as i64))
+                        .or_insert(0) |= rule::bit(dx, dy);
+                }
+            }
+        }
+        let next: HashSet<(i64, i64)> = neighbourhoods
+            .into_iter()
+            .filter(|&(_, neighbourhood)| self.rule.next_state(neighbourhood))
+            .map(|(point, _)| point)
+            .collect();
+        let survivors = next.intersection(&self.live).count() as u64;
+        self.changes = (
+            next.len() as u64 - survivors,
+            self.live.len() as u64 - survivors,
+        );
+        self.live = next;
+    }
+    /// 直前の1世代で (生まれたセル, 死んだセル) の数を返します。
+    pub fn births_and_deaths(&self) -> (u64, u64) {
+        self.changes
+    }
+    /// 盤面の状態のハッシュ値を返します。位置が違えば同じ形でも別の値になります。
+    pub fn state_hash(&self) -> u64 {
+        let (Snapshot(mut words), (x0, y0)) = self.shape();
+        words.extend([x0 as u64, y0 as u64]);
+        Snapshot(words).to_hash()
+    }
+    /// 生存セルを囲む最小の矩形 (min_x, min_y, max_x, max_y) を返します。
+    pub fn bounding_box(&self) -> Option<(i64, i64, i64, i64)> {
+        summary::bounding_box(self.live.iter().copied())
+    }
+    pub fn population(&self) -> u64 {
+        self.live.len() as u64
+    }
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+    /// 生存セルの座標を、左上が (0, 0) になるようにずらして並べた盤面の状態と、ずらす前の左上を返します。
+    /// 位置によらず同じ形になるため、移動する物体も繰り返しとして見つけられます。
+    fn shape(&self) -> (Snapshot, (i64, i64)) {
+        let (x0, y0, _, _) = self.bounding_box().unwrap_or_default();
+        // HashSet の列挙順は不定なので、並べ替えてから詰める
+        let mut cells: Vec<&(i64, i64)> = self.live.iter().collect();
+        cells.sort();
+        let snapshot = Snapshot(
+            cells
+                .into_iter()
+                .flat_map(|&(x, y)| [(x - x0) as u64, (y - y0) as u64])
+                .collect(),
+        );
+        (snapshot, (x0, y0))
+    }
+    /// 生存セルを囲む範囲だけを表示します。
+    pub fn show_board(&self) {
+        if let Some((x0, y0, x1, y1)) = self.bounding_box() {
+            println!("({}, {})", x0, y0);
+            for y in y0..=y1 {
+                print!("[");
+                for x in x0..=x1 {
+                    print!(
+                        "{}",
+                        if self.live.contains(&(x, y)) {
+                            "*"
+                        } else {
+                            " "
+                        }
+                    );
+                }
+                println!("]");
+            }
+        }
+        println!("======================================");
+    }
+    /// 現在の盤面が過去に現れていれば、その繰り返しを返します。
+    pub fn cycle(&self) -> Option<Cycle> {
+        let (shape, offset) = self.shape();
+        self.old_boards.find(self.generation, &shape, offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        soup::{Region, Soup, Symmetry},
+        Board,
+    };
+
+    fn sorted(mut cells: Vec<(i64, i64)>) -> Vec<(i64, i64)> {
+        cells.sort();
+        cells
+    }
+
+    /// 同じスープを、端に届かないほど大きい Board と SparseBoard で進め、毎世代の生存セルが一致することを確かめます。
+    fn assert_same_as_board(rule: &str) {
+        let (width, height) = (200, 200);
+        let soup = Soup {
+            density: 0.4,
+            region: Region::Centered {
+                width: 30,
+                height: 30,
+            },
+            symmetry: Symmetry::C1,
+            seed: 11,
+        };
+        let mut board = Board::new(width, height, 1);
+        board.set_rule(rule.parse().unwrap());
+        board.set_live(soup.cells(width, height));
+        let mut sparse = SparseBoard::new(1);
+        sparse.set_rule(rule.parse().unwrap());
+        sparse.set_live(
+            soup.cells(width, height)
+                .map(|(x, y)| (x as i64, y as i64))
+                .collect(),
+        );
+        for generation in 0..60 {
+            let expected = board
+                .to_pattern()
+                .cells
+                .into_iter()
+                .map(|(x, y)| (x as i64, y as i64))
+                .collect();
+            assert_eq!(
+                sorted(sparse.live_cells()),
+                sorted(expected),
+                "{} の {} 世代目",
+                rule,
+                generation
+            );
+            assert_eq!(sparse.population(), board.population());
+            board.step();
+            sparse.step();
+            assert_eq!(sparse.births_and_deaths(), board.births_and_deaths());
+        }
+    }
+
+    #[test]
+    fn matches_large_bounded_board() {
+        assert_same_as_board("B3/S23");
+        assert_same_as_board("B36/S23");
+        assert_same_as_board("B2n3/S23-q");
+    }
+
+    #[test]
+    fn glider_keeps_its_shape_in_negative_coordinates() {
+        // 左上に進むグライダー
+        let glider = vec![(0, 0), (1, 0), (2, 0), (0, 1), (1, 2)];
+        let mut sparse = SparseBoard::new(8);
+        sparse.set_live(glider.clone());
+        for _ in 0..40 {
+            sparse.step();
+        }
+        let moved: Vec<(i64, i64)> = glider.iter().map(|&(x, y)| (x - 10, y - 10)).collect();
+        assert_eq!(sorted(sparse.live_cells()), sorted(moved));
+        assert_eq!(sparse.bounding_box(), Some((-10, -10, -8, -8)));
+        assert_eq!(sparse.population(), 5);
+    }
+
+    /// 空の盤面に cells を置いて、繰り返しが見つかるまで進めます。
+    fn cycle_of(cells: &[(i64, i64)]) -> Cycle {
+        let mut sparse = SparseBoard::new(10);
+        sparse.set_live(cells.to_vec());
+        for _ in 0..10 {
+            sparse.step();
+            if let Some(cycle) = sparse.cycle() {
+                return cycle;
+            }
+        }
+        panic!("10 世代以内に繰り返さない");
+    }
+
+    #[test]
+    fn finds_spaceship_displacement() {
+        let glider = [(1, -1), (2, 0), (0, 1), (1, 1), (2, 1)];
+        let cycle = cycle_of(&glider);
+        assert_eq!((cycle.period, cycle.dx, cycle.dy), (4, 1, 1));
+        let lwss = [
+            (1, 0),
+            (4, 0),
+            (0, 1),
+            (0, 2),
+            (4, 2),
+            (0, 3),
+            (1, 3),
+            (2, 3),
+            (3, 3),
+        ];
+        let cycle = cycle_of(&lwss);
+        assert_eq!((cycle.period, cycle.dx, cycle.dy), (4, -2, 0));
+    }
+}
@@ -0,0 +1,123 @@
+//! 世代ごとの統計を CSV 形式で書き出します。
+
+use crate::universe::Universe;
+use std::io::{self, Write};
+
+/// CSV の見出し行
+const HEADER: &str = "generation,population,births,deaths,min_x,min_y,max_x,max_y,hash";
+
+/// 世代ごとの統計を CSV の1行ずつ書き出します。
+/// 書き出しに失敗したら、それ以降は書き出さずに最初のエラーを覚えておき、finish で返します。
+pub struct StatsLog {
+    out: Box<dyn Write>,
+    error: Option<io::Error>,
+}
+impl StatsLog {
+    /// 見出し行を書き出して記録を始めます。
+    pub fn new(out: impl Write + 'static) -> Self {
+        let mut out: Box<dyn Write> = Box::new(out);
+        let error = writeln!(out, "{}", HEADER).err();
+        StatsLog { out, error }
+    }
+    /// 現在の世代の生存セル数・生まれたセル数・死んだセル数・範囲・ハッシュ値を書き出します。
+    /// 生存セルがなければ、範囲の列は空になります。
+    pub fn record(&mut self, universe: &impl Universe) {
+        if self.error.is_some() {
+            return;
+        }
+        let (births, deaths) = universe.births_and_deaths();
+        let bounding_box = match universe.bounding_box() {
+            Some((min_x, min_y, max_x, max_y)) => {
+                format!("{},{},{},{}", min_x, min_y, max_x, max_y)
+            }
+            None => ",,,".to_string(),
+        };
+        self.error = writeln!(
+            self.out,
+            "{},{},{},{},{},{:016x}",
+            universe.generation(),
+            universe.population(),
+            births,
+            deaths,
+            bounding_box,
+            universe.state_hash()
+        )
+        .err();
+    }
+    /// 書き出しを終えます。途中で失敗していれば、そのエラーを返します。
+    pub fn finish(mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => self.out.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Board;
+    use std::{cell::RefCell, rc::Rc};
+
+    /// 書き出した内容をテストから読めるようにする出力先
+    #[derive(Clone, Default)]
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+    impl Shared {
+        fn lines(&self) -> Vec<String> {
+            String::from_utf8(self.0.borrow().clone())
+                .unwrap()
+                .lines()
+                .map(str::to_string)
+                .collect()
+        }
+    }
+
+    #[test]
+    fn records_blinker() {
+        let out = Shared::default();
+        let mut stats = StatsLog::new(out.clone());
+        let mut board = Board::new(10, 10, 10);
+        board.set_live([(4, 5), (5, 5), (6, 5)]);
+        let mut hashes = Vec::new();
+        for generation in 0..3 {
+            if generation > 0 {
+                board.step();
+            }
+            stats.record(&board);
+            hashes.push(format!("{:016x}", board.state_hash()));
+        }
+        stats.finish().unwrap();
+        assert_eq!(hashes[0], hashes[2]);
+        assert_ne!(hashes[0], hashes[1]);
+        assert_eq!(
+            out.lines(),
+            [
+                HEADER.to_string(),
+                format!("0,3,0,0,4,5,6,5,{}", hashes[0]),
+                format!("1,3,2,2,5,4,5,6,{}", hashes[1]),
+                format!("2,3,2,2,4,5,6,5,{}", hashes[2]),
+            ]
+        );
+    }
+
+    #[test]
+    fn leaves_bounding_box_empty_without_cells() {
+        let out = Shared::default();
+        let mut stats = StatsLog::new(out.clone());
+        let board = Board::new(10, 10, 10);
+        stats.record(&board);
+        stats.finish().unwrap();
+        assert_eq!(
+            out.lines()[1],
+            format!("0,0,0,0,,,,,{:016x}", board.state_hash())
+        );
+    }
+}
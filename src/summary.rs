@@ -0,0 +1,313 @@
+use crate::{
+    census::{Census, ObjectKind},
+    tiles::TileStats,
+};
+use std::fmt;
+
+/// 座標の一覧を囲む最小の矩形 (min_x, min_y, max_x, max_y) を返します。
+pub fn bounding_box(cells: impl IntoIterator<Item = (i64, i64)>) -> Option<(i64, i64, i64, i64)> {
+    let mut cells = cells.into_iter();
+    let (x, y) = cells.next()?;
+    Some(cells.fold((x, y, x, y), |(x0, y0, x1, y1), (x, y)| {
+        (x0.min(x), y0.min(y), x1.max(x), y1.max(y))
+    }))
+}
+
+/// period 世代で (dx, dy) 移動する物体の速さを、c/4 や 2c/5 のような形で返します。
+/// 斜めに動くものには「斜め」、縦横どちらでもない向きに動くものには「斜行」を付けます。
+pub fn speed(period: u64, dx: i64, dy: i64) -> String {
+    fn gcd(a: u64, b: u64) -> u64 {
+        if b == 0 {
+            a
+        } else {
+            gcd(b, a % b)
+        }
+    }
+    let distance = dx.unsigned_abs().max(dy.unsigned_abs());
+    let divisor = gcd(distance, period).max(1);
+    let (distance, period) = (distance / divisor, period / divisor);
+    let mut speed = match distance {
+        1 => format!("c/{}", period),
+        n => format!("{}c/{}", n, period),
+    };
+    if dx != 0 && dy != 0 {
+        speed += if dx.abs() == dy.abs() {
+            " 斜め"
+        } else {
+            " 斜行"
+        };
+    }
+    speed
+}
+
+/// 文字列を、'"' や '\\'、制御文字をエスケープした JSON の文字列にします。
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out += "\\\"",
+            '\\' => out += "\\\\",
+            '\n' => out += "\\n",
+            c if c.is_control() => out += &format!("\\u{:04x}", c as u32),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// 実行が終わった理由です。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RunOutcome {
+    /// 生存セルがなくなった
+    Extinct,
+    /// 盤面が変化しなくなった
+    StillLife,
+    /// period 世代ごとに同じ盤面を繰り返している
+    Oscillator { period: u64 },
+    /// period 世代ごとに (dx, dy) だけ移動した同じ盤面を繰り返している
+    Spaceship { period: u64, dx: i64, dy: i64 },
+    /// 最大世代数に達した
+    MaxGenerations,
+    /// 利用者が中断した
+    UserAbort,
+}
+impl fmt::Display for RunOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RunOutcome::Extinct => write!(f, "全滅"),
+            RunOutcome::StillLife => write!(f, "固定物"),
+            RunOutcome::Oscillator { period } => write!(f, "周期{}の振動子", period),
+            RunOutcome::Spaceship { period, dx, dy } => write!(
+                f,
+                "周期{}の宇宙船 ({}, {}) 移動, 速さ {}",
+                period,
+                dx,
+                dy,
+                speed(*period, *dx, *dy)
+            ),
+            RunOutcome::MaxGenerations => write!(f, "最大世代数に到達"),
+            RunOutcome::UserAbort => write!(f, "中断"),
+        }
+    }
+}
+
+/// 実行結果のまとめです。
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunSummary {
+    pub outcome: RunOutcome,
+    /// 繰り返しが始まった世代(固定物・振動子・宇宙船のときだけ)
+    pub cycle_start: Option<u64>,
+    pub generations: u64,
+    pub population: u64,
+    pub peak_population: u64,
+    /// 生存セルを囲む最小の矩形 (min_x, min_y, max_x, max_y)
+    pub bounding_box: Option<(i64, i64, i64, i64)>,
+    /// 初期配置をランダムに埋めたときのシード
+    pub seed: Option<u64>,
+    /// 最後の盤面の物体の内訳(--census が指定されたときだけ)
+    pub census: Option<Census>,
+    /// 最後の盤面全体の apgcode(--census が指定され、種類が分かったときだけ)
+    pub apgcode: Option<String>,
+    /// 再計算したタイルと省略したタイルの数(--engine dense のときだけ)
+    pub tiles: Option<TileStats>,
+}
+impl RunSummary {
+    /// バッチ処理用に JSON 形式の文字列にします。
+    pub fn to_json(&self) -> String {
+        let (outcome, period) = match self.outcome {
+            RunOutcome::Extinct => ("extinct", None),
+            RunOutcome::StillLife => ("still_life", Some(1)),
+            RunOutcome::Oscillator { period } => ("oscillator", Some(period)),
+            RunOutcome::Spaceship { period, .. } => ("spaceship", Some(period)),
+            RunOutcome::MaxGenerations => ("max_generations", None),
+            RunOutcome::UserAbort => ("user_abort", None),
+        };
+        let number = |n: Option<u64>| n.map_or("null".to_string(), |n| n.to_string());
+        let string = |s: Option<&str>| s.map_or("null".to_string(), json_string);
+        let bounding_box = match self.bounding_box {
+            Some((min_x, min_y, max_x, max_y)) => format!(
+                "{{\"min_x\":{},\"min_y\":{},\"max_x\":{},\"max_y\":{}}}",
+                min_x, min_y, max_x, max_y
+            ),
+            None => "null".to_string(),
+        };
+        let (displacement, speed) = match self.outcome {
+            RunOutcome::Spaceship { period, dx, dy } => (
+                format!("{{\"dx\":{},\"dy\":{}}}", dx, dy),
+                json_string(&speed(period, dx, dy)),
+            ),
+            _ => ("null".to_string(), "null".to_string()),
+        };
+        let census = match &self.census {
+            Some(census) => {
+                let entries: Vec<String> = census
+                    .entries
+                    .iter()
+                    .map(|entry| {
+                        let (kind, period) = match entry.kind {
+                            ObjectKind::StillLife => ("still_life", Some(1)),
+                            ObjectKind::Oscillator { period } => ("oscillator", Some(period)),
+                            ObjectKind::Spaceship { period, .. } => ("spaceship", Some(period)),
+                            ObjectKind::Unknown => ("unknown", None),
+                        };
+                        format!(
+                            "{{\"name\":{},\"apgcode\":{},\"kind\":\"{}\",\"period\":{},\"cells\":{},\"count\":{}}}",
+                            string(entry.name),
+                            string(entry.apgcode.as_deref()),
+                            kind,
+                            number(period),
+                            entry.cells.len(),
+                            entry.count
+                        )
+                    })
+                    .collect();
+                format!("[{}]", entries.join(","))
+            }
+            None => "null".to_string(),
+        };
+        let tiles = match self.tiles {
+            Some(tiles) => format!(
+                "{{\"computed\":{},\"skipped\":{}}}",
+                tiles.computed, tiles.skipped
+            ),
+            None => "null".to_string(),
+        };
+        format!(
+            "{{\"outcome\":\"{}\",\"period\":{},\"displacement\":{},\"speed\":{},\"cycle_start\":{},\"generations\":{},\"population\":{},\"peak_population\":{},\"bounding_box\":{},\"seed\":{},\"census\":{},\"apgcode\":{},\"tiles\":{}}}",
+            outcome,
+            number(period),
+            displacement,
+            speed,
+            number(self.cycle_start),
+            self.generations,
+            self.population,
+            self.peak_population,
+            bounding_box,
+            number(self.seed),
+            census,
+            string(self.apgcode.as_deref()),
+            tiles
+        )
+    }
+}
+impl fmt::Display for RunSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "結果: {}", self.outcome)?;
+        if let Some(start) = self.cycle_start {
+            write!(f, " (第{}世代から)", start)?;
+        }
+        writeln!(f)?;
+        writeln!(f, "世代数: {}", self.generations)?;
+        writeln!(
+            f,
+            "生存セル数: {} (最大 {})",
+            self.population, self.peak_population
+        )?;
+        match self.bounding_box {
+            Some((min_x, min_y, max_x, max_y)) => write!(
+                f,
+                "範囲: ({}, {}) - ({}, {}) [{} x {}]",
+                min_x,
+                min_y,
+                max_x,
+                max_y,
+                max_x - min_x + 1,
+                max_y - min_y + 1
+            ),
+            None => write!(f, "範囲: なし"),
+        }?;
+        if let Some(seed) = self.seed {
+            write!(f, "\nシード: {}", seed)?;
+        }
+        if let Some(tiles) = self.tiles {
+            write!(f, "\n{}", tiles)?;
+        }
+        if let Some(code) = &self.apgcode {
+            write!(f, "\napgcode: {}", code)?;
+        }
+        if let Some(census) = &self.census {
+            write!(f, "\n{}", census)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(outcome: RunOutcome) -> RunSummary {
+        RunSummary {
+            outcome,
+            cycle_start: None,
+            generations: 0,
+            population: 0,
+            peak_population: 0,
+            bounding_box: None,
+            seed: None,
+            census: None,
+            apgcode: None,
+            tiles: None,
+        }
+    }
+
+    #[test]
+    fn writes_json() {
+        assert_eq!(
+            summary(RunOutcome::Extinct).to_json(),
+            "{\"outcome\":\"extinct\",\"period\":null,\"displacement\":null,\"speed\":null,\"cycle_start\":null,\"generations\":0,\"population\":0,\"peak_population\":0,\"bounding_box\":null,\"seed\":null,\"census\":null,\"apgcode\":null,\"tiles\":null}"
+        );
+        let glider = RunSummary {
+            cycle_start: Some(3),
+            generations: 7,
+            population: 5,
+            peak_population: 6,
+            bounding_box: Some((-1, 2, 1, 4)),
+            seed: Some(42),
+            apgcode: Some("xq4_153".to_string()),
+            tiles: Some(TileStats {
+                computed: 10,
+                skipped: 30,
+            }),
+            ..summary(RunOutcome::Spaceship {
+                period: 4,
+                dx: 1,
+                dy: 1,
+            })
+        };
+        assert_eq!(
+            glider.to_json(),
+            "{\"outcome\":\"spaceship\",\"period\":4,\"displacement\":{\"dx\":1,\"dy\":1},\"speed\":\"c/4 斜め\",\"cycle_start\":3,\"generations\":7,\"population\":5,\"peak_population\":6,\"bounding_box\":{\"min_x\":-1,\"min_y\":2,\"max_x\":1,\"max_y\":4},\"seed\":42,\"census\":null,\"apgcode\":\"xq4_153\",\"tiles\":{\"computed\":10,\"skipped\":30}}"
+        );
+    }
+
+    #[test]
+    fn escapes_json_strings() {
+        assert_eq!(json_string("xs4_33"), "\"xs4_33\"");
+        assert_eq!(json_string("a\"b\\c\nd\u{1}"), "\"a\\\"b\\\\c\\nd\\u0001\"");
+        let summary = RunSummary {
+            apgcode: Some("\"".to_string()),
+            ..summary(RunOutcome::MaxGenerations)
+        };
+        assert!(summary.to_json().contains("\"apgcode\":\"\\\"\""));
+    }
+
+    #[test]
+    fn describes_speed() {
+        assert_eq!(speed(4, 1, 1), "c/4 斜め");
+        assert_eq!(speed(4, -1, 1), "c/4 斜め");
+        assert_eq!(speed(4, -2, 0), "c/2");
+        assert_eq!(speed(5, 0, 2), "2c/5");
+        assert_eq!(speed(6, 2, 1), "c/3 斜行");
+        assert_eq!(
+            RunOutcome::Spaceship {
+                period: 4,
+                dx: 1,
+                dy: -1
+            }
+            .to_string(),
+            "周期4の宇宙船 (1, -1) 移動, 速さ c/4 斜め"
+        );
+    }
+}
@@ -0,0 +1,199 @@
+//! 矩形の範囲にある生存セルの数を、範囲の大きさによらず定数時間で求めるための累積和の表です。
+
+/// 各位置より左上の矩形にある生存セルの数を並べた表(summed-area table)です。
+/// 盤面の外側 margin セルの帯も含めて作るため、盤面から margin セルまではみ出した範囲も数えられます。
+pub struct SummedArea {
+    margin: usize,
+    /// 1行の要素数(盤面の幅 + 2 * margin + 1)
+    stride: usize,
+    sums: Vec<u32>,
+}
+impl SummedArea {
+    /// width x height の盤面の生存セル(is_live)から表を作ります。
+    /// wrap が true なら外側の帯に反対側の端のセルを写し、false なら外側を死んだセルとして扱います。
+    pub fn new(
+        width: usize,
+        height: usize,
+        margin: usize,
+        wrap: bool,
+        is_live: impl Fn(usize, usize) -> bool,
+    ) -> Self {
+        let (w, h) = (width + 2 * margin, height + 2 * margin);
+        let stride = w + 1;
+        let mut sums = vec![0; stride * (h + 1)];
+        for y in 0..h {
+            let by = y as isize - margin as isize;
+            let mut row = 0;
+            for x in 0..w {
+                let bx = x as isize - margin as isize;
+                let live = if wrap {
+                    is_live(
+                        bx.rem_euclid(width as isize) as usize,
+                        by.rem_euclid(height as isize) as usize,
+                    )
+                } else {
+                    (0..width as isize).contains(&bx)
+                        && (0..height as isize).contains(&by)
+                        && is_live(bx as usize, by as usize)
+                };
+                row += live as u32;
+                sums[(y + 1) * stride + x + 1] = sums[y * stride + x + 1] + row;
+            }
+        }
+        SummedArea {
+            margin,
+            stride,
+            sums,
+        }
+    }
+    /// 盤面の x0..x1, y0..y1 (x1, y1 は含まない)の範囲にある生存セルの数を返します。
+    pub fn sum(&self, x0: isize, y0: isize, x1: isize, y1: isize) -> u32 {
+        let index = |x: isize, y: isize| {
+            let margin = self.margin as isize;
+            (y + margin) as usize * self.stride + (x + margin) as usize
+        };
+        self.sums[index(x1, y1)] + self.sums[index(x0, y0)]
+            - self.sums[index(x1, y0)]
+            - self.sums[index(x0, y1)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cycle,
+        rule::{Rule, Shape},
+        soup::{Region, Soup, Symmetry},
+        Board, Topology,
+    };
+
+    #[test]
+    fn sums_match_brute_force() {
+        let (width, height, margin) = (9, 7, 3);
+        let is_live = |x: usize, y: usize| (x * 7 + y * 13) % 5 < 2;
+        for wrap in [false, true] {
+            let sums = SummedArea::new(width, height, margin, wrap, is_live);
+            let live = |x: isize, y: isize| {
+                let (w, h) = (width as isize, height as isize);
+                if wrap {
+                    is_live(x.rem_euclid(w) as usize, y.rem_euclid(h) as usize)
+                } else {
+                    (0..w).contains(&x) && (0..h).contains(&y) && is_live(x as usize, y as usize)
+                }
+            };
+            let m = margin as isize;
+            let xs = -m..=width as isize + m;
+            let ys = -m..=height as isize + m;
+            for x0 in xs.clone() {
+                for x1 in (x0..=width as isize + m).step_by(2) {
+                    for y0 in ys.clone() {
+                        for y1 in y0..=height as isize + m {
+                            let expected = (y0..y1)
+                                .flat_map(|y| (x0..x1).map(move |x| (x, y)))
+                                .filter(|&(x, y)| live(x, y))
+                                .count();
+                            assert_eq!(
+                                sums.sum(x0, y0, x1, y1) as usize,
+                                expected,
+                                "wrap {} の ({}, {})..({}, {})",
+                                wrap,
+                                x0,
+                                y0,
+                                x1,
+                                y1
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// 盤面の状態 states の次の世代を、範囲内の生存セルを1つずつ数えて求めます。
+    fn brute_force_step(states: &[Vec<u8>], rule: &Rule, topology: Topology) -> Vec<Vec<u8>> {
+        let ltl = rule.larger_than_life().unwrap();
+        let r = ltl.range as isize;
+        let (height, width) = (states.len() as isize, states[0].len() as isize);
+        let inside = |dx: isize, dy: isize| match ltl.shape {
+            Shape::Moore => true,
+            Shape::VonNeumann => dx.abs() + dy.abs() <= r,
+            // 中央からの距離が r + 0.5 より小さい
+            Shape::Circular => 4 * (dx * dx + dy * dy) < (2 * r + 1) * (2 * r + 1),
+        };
+        let live = |x: isize, y: isize| {
+            let (x, y) = match topology {
+                Topology::Bounded => (x, y),
+                Topology::Torus => (x.rem_euclid(width), y.rem_euclid(height)),
+            };
+            (0..width).contains(&x)
+                && (0..height).contains(&y)
+                && states[y as usize][x as usize] == 1
+        };
+        (0..height)
+            .map(|y| {
+                (0..width)
+                    .map(|x| {
+                        let count = (-r..=r)
+                            .flat_map(|dy| (-r..=r).map(move |dx| (dx, dy)))
+                            .filter(|&(dx, dy)| inside(dx, dy) && live(x + dx, y + dy))
+                            .count();
+                        rule.next(states[y as usize][x as usize], count as u16)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn states(board: &Board, width: usize, height: usize) -> Vec<Vec<u8>> {
+        let snapshot = board.snapshot();
+        let bits = board.rule.state_bits();
+        (0..height)
+            .map(|y| {
+                (0..width)
+                    .map(|x| cycle::unpack(&snapshot.0, bits, y * width + x))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn board_matches_brute_force_count() {
+        let (width, height) = (24, 20);
+        let soup = Soup {
+            density: 0.5,
+            region: Region::Whole,
+            symmetry: Symmetry::C1,
+            seed: 5,
+        };
+        for text in [
+            "R2,C0,M0,S3..5,B3..4,NM",
+            "R2,C0,M1,S4..7,B4..5,NN",
+            "R3,C0,M1,S8..16,B9..12,NC",
+            "R2,C3,M0,S3..6,B4..5,NM",
+            "R3,C4,M1,S6..12,B7..9,NC",
+            "R2,C5,M0,S2..4,B3..4,NN",
+        ] {
+            for topology in [Topology::Bounded, Topology::Torus] {
+                let rule: Rule = text.parse().unwrap();
+                let mut board = Board::new(width, height, 1);
+                board.set_rule(rule.clone());
+                board.set_topology(topology);
+                board.set_live(soup.cells(width, height));
+                let mut expected = states(&board, width, height);
+                for generation in 1..=8 {
+                    board.step();
+                    expected = brute_force_step(&expected, &rule, topology);
+                    assert_eq!(
+                        states(&board, width, height),
+                        expected,
+                        "{} ({:?}) の {} 世代目",
+                        text,
+                        topology,
+                        generation
+                    );
+                }
+            }
+        }
+    }
+}
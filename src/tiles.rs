@@ -0,0 +1,98 @@
+use std::{fmt, ops::Range};
+
+/// タイルの一辺のセル数
+pub const TILE_SIZE: usize = 16;
+
+/// 世代を進めるときに、再計算したタイルと省略したタイルの数です。
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TileStats {
+    pub computed: u64,
+    pub skipped: u64,
+}
+impl fmt::Display for TileStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.computed + self.skipped;
+        let ratio = if total == 0 {
+            0.0
+        } else {
+            self.skipped as f64 * 100.0 / total as f64
+        };
+        write!(
+            f,
+            "再計算したタイル: {}  省略したタイル: {} ({:.1}%)",
+            self.computed, self.skipped, ratio
+        )
+    }
+}
+
+/// 盤面を TILE_SIZE x TILE_SIZE のタイルに分け、前の世代で変化したタイルを覚えておきます。
+/// 変化したタイルとその周りのタイル以外は、次の世代でも変化しないため計算を省略できます。
+pub struct Tiles {
+    width: usize,
+    height: usize,
+    columns: usize,
+    rows: usize,
+    /// 前の世代で変化したタイル
+    dirty: Vec<bool>,
+}
+impl Tiles {
+    /// width x height の盤面のタイルを、すべて変化したものとして作ります。
+    pub fn new(width: usize, height: usize) -> Self {
+        let columns = width.div_ceil(TILE_SIZE);
+        let rows = height.div_ceil(TILE_SIZE);
+        Tiles {
+            width,
+            height,
+            columns,
+            rows,
+            dirty: vec![true; columns * rows],
+        }
+    }
+    /// 横に並ぶタイルの数を返します。タイルの番号は y / TILE_SIZE * columns + x / TILE_SIZE です。
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+    /// すべてのタイルを変化したものとします。盤面をまとめて書き換えたときに使います。
+    pub fn mark_all(&mut self) {
+        self.dirty.fill(true);
+    }
+    /// (x, y) のセルを含むタイルを変化したものとします。
+    pub fn mark(&mut self, x: usize, y: usize) {
+        self.dirty[y / TILE_SIZE * self.columns + x / TILE_SIZE] = true;
+    }
+    /// i 番目のタイルを変化したものとします。
+    pub fn mark_tile(&mut self, i: usize) {
+        self.dirty[i] = true;
+    }
+    /// 次の世代で計算が必要なタイル(変化したタイルとその周り)を返し、変化の記録を空にします。
+    /// wrap が true なら、盤面の反対側の端のタイルも周りとして扱います。
+    pub fn take_active(&mut self, wrap: bool) -> Vec<bool> {
+        let mut active = vec![false; self.dirty.len()];
+        let (columns, rows) = (self.columns as isize, self.rows as isize);
+        for (i, _) in self.dirty.iter().enumerate().filter(|(_, &dirty)| dirty) {
+            let (tx, ty) = ((i % self.columns) as isize, (i / self.columns) as isize);
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    let (mut x, mut y) = (tx + dx, ty + dy);
+                    if wrap {
+                        x = x.rem_euclid(columns);
+                        y = y.rem_euclid(rows);
+                    } else if !(0..columns).contains(&x) || !(0..rows).contains(&y) {
+                        continue;
+                    }
+                    active[(y * columns + x) as usize] = true;
+                }
+            }
+        }
+        self.dirty.fill(false);
+        active
+    }
+    /// i 番目のタイルに含まれるセルの (x の範囲, y の範囲) を返します。
+    pub fn cells(&self, i: usize) -> (Range<usize>, Range<usize>) {
+        let (x0, y0) = (i % self.columns * TILE_SIZE, i / self.columns * TILE_SIZE);
+        (
+            x0..self.width.min(x0 + TILE_SIZE),
+            y0..self.height.min(y0 + TILE_SIZE),
+        )
+    }
+}
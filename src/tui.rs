@@ -0,0 +1,395 @@
+//! 端末の画面全体を使って、盤面をその場で描き直しながら世代を進める対話モードです。
+//!
+//! 外部クレートを使わず、端末の設定は stty コマンドで、描画は ANSI エスケープシーケンスで行います。
+
+use crate::{
+    cli::Config,
+    editor::{self, EditResult, Viewport},
+    rule::Neighbourhood,
+    run,
+    summary::{RunOutcome, RunSummary},
+    Board,
+};
+use std::{
+    io::{self, Read, Write},
+    process::{Command, Stdio},
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    thread,
+    time::Duration,
+};
+
+/// 1世代ごとの待ち時間(ミリ秒)の段階
+const DELAYS: [u64; 9] = [0, 10, 25, 50, 100, 200, 400, 700, 1000];
+/// 最初の待ち時間の段階
+const DEFAULT_DELAY: usize = 4;
+/// 盤面以外に使う行数(状態表示・巻き戻しのつまみ・キー操作の説明)
+const STATUS_LINES: usize = 3;
+/// --rewind が指定されていないときに、巻き戻せるように覚えておく世代数
+pub const DEFAULT_REWIND: usize = 1000;
+/// 巻き戻しのつまみの幅
+const SCRUBBER_WIDTH: usize = 40;
+/// < / > で移動する世代数
+const JUMP: u64 = 10;
+
+/// 押されたキーです。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Key {
+    Char(char),
+    Up,
+    Down,
+    Left,
+    Right,
+    Enter,
+    Escape,
+}
+
+/// 端末を対話モードに切り替え、drop されたときに元に戻します。
+pub struct Terminal {
+    /// 切り替える前の stty の設定
+    saved: String,
+    rows: usize,
+    columns: usize,
+}
+impl Terminal {
+    pub fn enter() -> io::Result<Terminal> {
+        let saved = stty(&["-g"])?;
+        // 行単位の入力・エコー・Ctrl-C によるシグナルを止め、1文字ずつ読めるようにする
+        stty(&["-icanon", "-echo", "-isig", "min", "1"])?;
+        let size = stty(&["size"]).unwrap_or_default();
+        let mut size = size.split_whitespace().filter_map(|n| n.parse().ok());
+        let terminal = Terminal {
+            saved,
+            rows: size.next().unwrap_or(24),
+            columns: size.next().unwrap_or(80),
+        };
+        // 代替画面に切り替えてカーソルを隠す
+        print!("\x1b[?1049h\x1b[?25l\x1b[2J");
+        io::stdout().flush()?;
+        Ok(terminal)
+    }
+    /// 盤面の表示に使える (行数, 桁数) を返します。
+    /// 端末が小さすぎても、カーソルのあるセルを表示できるように少なくとも 1 にします。
+    pub fn board_area(&self) -> (usize, usize) {
+        (
+            self.rows.saturating_sub(STATUS_LINES).max(1),
+            self.columns.max(1),
+        )
+    }
+    /// 画面の左上から lines を描き直します。
+    pub fn draw(&self, lines: &[String]) -> io::Result<()> {
+        let mut frame = String::from("\x1b[H");
+        for line in lines.iter().take(self.rows) {
+            frame += line;
+            frame += "\x1b[K\n";
+        }
+        frame += "\x1b[J";
+        let mut stdout = io::stdout();
+        stdout.write_all(frame.as_bytes())?;
+        stdout.flush()
+    }
+}
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        print!("\x1b[?25h\x1b[?1049l");
+        let _ = io::stdout().flush();
+        let _ = stty(&[self.saved.as_str()]);
+    }
+}
+
+/// 端末を操作する stty コマンドを実行し、その出力を返します。
+fn stty(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other("対話モードには端末が必要です"));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// 標準入力からキーを読み続けるスレッドを起動します。
+pub fn spawn_key_reader() -> Receiver<Key> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut stdin = io::stdin().lock();
+        let mut buffer = [0u8; 64];
+        // 矢印キーは ESC [ A〜D の3バイトがまとめて届くので、1回に読めた分ごとに解釈する
+        while let Ok(n @ 1..) = stdin.read(&mut buffer) {
+            let mut bytes = buffer[..n].iter().copied();
+            while let Some(byte) = bytes.next() {
+                let key = match byte {
+                    0x1b => match (bytes.next(), bytes.next()) {
+                        (Some(b'['), Some(b'A')) => Key::Up,
+                        (Some(b'['), Some(b'B')) => Key::Down,
+                        (Some(b'['), Some(b'C')) => Key::Right,
+                        (Some(b'['), Some(b'D')) => Key::Left,
+                        _ => Key::Escape,
+                    },
+                    b'\r' | b'\n' => Key::Enter,
+                    // Ctrl-C は終了として扱う
+                    0x03 => Key::Char('q'),
+                    byte => Key::Char(byte as char),
+                };
+                if sender.send(key).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+    receiver
+}
+
+/// 六角形の近傍のルールでは、セルの間に空白を入れ、奇数行を半セル右にずらして表示します。
+fn hexagonal(board: &Board) -> bool {
+    board.rule.neighbourhood() == Neighbourhood::Hexagonal
+}
+
+/// 画面の columns 文字の幅に表示できるセルの数を返します。
+pub fn visible_cells(board: &Board, columns: usize) -> usize {
+    if hexagonal(board) {
+        (columns.saturating_sub(1) / 2).max(1)
+    } else {
+        columns
+    }
+}
+
+/// render_board で作った盤面の y 行目の文字列で、表示範囲の左から i 番目のセルが何文字目にあるかを返します。
+pub fn cell_position(board: &Board, y: usize, i: usize) -> usize {
+    if hexagonal(board) {
+        2 * i + y % 2
+    } else {
+        i
+    }
+}
+
+/// 盤面の (x0, y0) から表示できる範囲を、1行ずつの文字列にします。
+pub fn render_board(
+    board: &Board,
+    x0: usize,
+    y0: usize,
+    rows: usize,
+    columns: usize,
+) -> Vec<String> {
+    let cells = visible_cells(board, columns);
+    (y0..board.height.min(y0 + rows))
+        .map(|y| {
+            let mut line = String::new();
+            for (i, x) in (x0..board.width.min(x0 + cells)).enumerate() {
+                while line.len() < cell_position(board, y, i) {
+                    line.push(' ');
+                }
+                line.push(
+                    board.array[y + 1][x + 1]
+                        .now_state
+                        .glyph(board.rule.states(), '.'),
+                );
+            }
+            line
+        })
+        .collect()
+}
+
+/// 巻き戻せる範囲の中で、今の世代がどこにあるかを表す1行を作ります。
+fn scrubber(board: &Board) -> String {
+    match board.rewind_range() {
+        Some((oldest, newest)) if newest > oldest => {
+            let position = ((board.generation() - oldest) * (SCRUBBER_WIDTH as u64 - 1)
+                / (newest - oldest)) as usize;
+            let bar: String = (0..SCRUBBER_WIDTH)
+                .map(|i| if i == position { '|' } else { '-' })
+                .collect();
+            format!("巻き戻し: {} [{}] {}", oldest, bar, newest)
+        }
+        _ => "巻き戻し: なし".to_string(),
+    }
+}
+
+/// 最大世代数に達していなければ1世代進め、実行が終わったかを調べます。進めたら true を返します。
+fn step_once(
+    board: &mut Board,
+    config: &Config,
+    peak_population: &mut u64,
+    finished: &mut Option<(RunOutcome, Option<u64>)>,
+) -> bool {
+    if config
+        .max_generations
+        .is_some_and(|max| board.generation() >= max)
+    {
+        return false;
+    }
+    board.step();
+    *peak_population = (*peak_population).max(board.population());
+    if finished.is_none() {
+        *finished = run::check(board);
+    }
+    if let Some(max) = config.max_generations {
+        if finished.is_none() && board.generation() >= max {
+            *finished = Some((RunOutcome::MaxGenerations, None));
+        }
+    }
+    true
+}
+
+/// 対話モードで盤面を進めます。
+/// スペースで一時停止/再開、n で1世代だけ進め、+/- で速さを変え、e で編集モードに入り、q で終了します。
+/// --edit が指定されていれば、編集モードから始めます。
+pub fn run(board: &mut Board, config: &Config) -> io::Result<RunSummary> {
+    let terminal = Terminal::enter()?;
+    let keys = spawn_key_reader();
+    let mut viewport = Viewport::default();
+    if config.edit
+        && editor::edit(board, &terminal, &keys, &mut viewport, config)? == EditResult::Quit
+    {
+        drop(terminal);
+        return Ok(run::summarize(
+            board,
+            RunOutcome::UserAbort,
+            None,
+            board.population(),
+        ));
+    }
+    let mut paused = false;
+    let mut delay = DEFAULT_DELAY;
+    let mut peak_population = board.population();
+    let mut finished: Option<(RunOutcome, Option<u64>)> = None;
+
+    loop {
+        let (rows, columns) = terminal.board_area();
+        let mut lines = render_board(board, viewport.x0, viewport.y0, rows, columns);
+        let state = match finished {
+            Some((outcome, _)) => outcome.to_string(),
+            None if paused => "一時停止".to_string(),
+            None => format!("{}ms/世代", DELAYS[delay]),
+        };
+        lines.push(format!(
+            "世代: {}  生存セル: {}  ルール: {}  [{}]",
+            board.generation(),
+            board.population(),
+            board.rule,
+            state
+        ));
+        lines.push(scrubber(board));
+        lines.push(
+            "space: 一時停止/再開  n: 1世代進める  ←/→ </>: 巻き戻し/早送り  +/-: 速さ  e: 編集  q: 終了"
+                .to_string(),
+        );
+        terminal.draw(&lines)?;
+
+        // 自動で進める間は待ち時間だけキーを待ち、止まっている間はキーが押されるまで待つ
+        let running = !paused && finished.is_none();
+        let key = if running {
+            match keys.recv_timeout(Duration::from_millis(DELAYS[delay])) {
+                Ok(key) => Some(key),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => Some(Key::Char('q')),
+            }
+        } else {
+            Some(keys.recv().unwrap_or(Key::Char('q')))
+        };
+        let step = match key {
+            None => true,
+            Some(Key::Char('q')) => break,
+            Some(Key::Char(' ')) => {
+                paused = !paused;
+                false
+            }
+            Some(Key::Char('n')) => {
+                paused = true;
+                true
+            }
+            Some(Key::Char('+')) => {
+                delay = delay.saturating_sub(1);
+                false
+            }
+            Some(Key::Char('-')) => {
+                delay = (delay + 1).min(DELAYS.len() - 1);
+                false
+            }
+            Some(Key::Left) => {
+                paused = true;
+                if board.step_back() {
+                    finished = None;
+                }
+                false
+            }
+            Some(Key::Char('<')) => {
+                paused = true;
+                // 履歴に残っている範囲までしか戻れない
+                let generation = board.generation();
+                let oldest = board
+                    .rewind_range()
+                    .map_or(generation, |(oldest, _)| oldest);
+                if board.jump_to(generation.saturating_sub(JUMP).max(oldest)) && oldest < generation
+                {
+                    finished = None;
+                }
+                false
+            }
+            Some(key @ (Key::Right | Key::Char('>'))) => {
+                paused = true;
+                let count = if key == Key::Right { 1 } else { JUMP };
+                for _ in 0..count {
+                    if !step_once(board, config, &mut peak_population, &mut finished) {
+                        break;
+                    }
+                }
+                false
+            }
+            Some(Key::Char('e')) => {
+                if editor::edit(board, &terminal, &keys, &mut viewport, config)? == EditResult::Quit
+                {
+                    break;
+                }
+                // 書き換えた盤面から数え直す
+                peak_population = board.population();
+                finished = None;
+                false
+            }
+            Some(_) => false,
+        };
+        if step {
+            step_once(board, config, &mut peak_population, &mut finished);
+        }
+    }
+    drop(terminal);
+    let (outcome, cycle_start) = finished.unwrap_or((RunOutcome::UserAbort, None));
+    Ok(run::summarize(board, outcome, cycle_start, peak_population))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_hexagonal_rows_offset() {
+        let mut board = Board::new(3, 3, 1);
+        board.set_live([(0, 0), (1, 1), (2, 2)]);
+        assert_eq!(render_board(&board, 0, 0, 3, 80), ["*..", ".*.", "..*"]);
+        board.set_rule("B2/S34H".parse().unwrap());
+        let lines = render_board(&board, 0, 0, 3, 80);
+        assert_eq!(lines, ["* . .", " . * .", ". . *"]);
+        assert_eq!(&lines[1][cell_position(&board, 1, 1)..][..1], "*");
+        // 幅 6 文字には、ずらした行の分を空けて 2 セルまで表示する
+        assert_eq!(visible_cells(&board, 6), 2);
+        assert_eq!(render_board(&board, 1, 1, 1, 6), [" * ."]);
+    }
+
+    #[test]
+    fn stepping_stops_at_max_generations() {
+        let config = Config {
+            max_generations: Some(3),
+            ..Config::default()
+        };
+        let mut board = Board::new(20, 20, 10);
+        // 全滅も繰り返しもしないうちに最大世代数に達するグライダー
+        board.set_live([(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)]);
+        let (mut peak_population, mut finished) = (board.population(), None);
+        for _ in 0..JUMP {
+            step_once(&mut board, &config, &mut peak_population, &mut finished);
+        }
+        assert_eq!(board.generation(), 3);
+        assert_eq!(finished, Some((RunOutcome::MaxGenerations, None)));
+    }
+}
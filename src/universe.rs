@@ -0,0 +1,98 @@
+use crate::{bitboard::BitBoard, cycle::Cycle, sparse::SparseBoard, Board};
+
+/// 世代を1つずつ進められる盤面の共通の操作です。
+/// 実行ループはこのトレイトを通して盤面を扱います。
+pub trait Universe {
+    /// 1世代進めます。
+    fn step(&mut self);
+    fn show_board(&self);
+    fn generation(&self) -> u64;
+    /// 生存セルの数を返します。
+    fn population(&self) -> u64;
+    /// 生存セルを囲む最小の矩形 (min_x, min_y, max_x, max_y) を返します。
+    fn bounding_box(&self) -> Option<(i64, i64, i64, i64)>;
+    /// 以前に現れた盤面に戻っていれば、その繰り返しを返します。
+    fn cycle(&self) -> Option<Cycle>;
+    /// 直前の1世代で (生まれたセル, 死んだセル) の数を返します。
+    fn births_and_deaths(&self) -> (u64, u64);
+    /// 盤面の状態のハッシュ値を返します。
+    fn state_hash(&self) -> u64;
+}
+impl Universe for Board {
+    fn step(&mut self) {
+        Board::step(self)
+    }
+    fn show_board(&self) {
+        Board::show_board(self)
+    }
+    fn generation(&self) -> u64 {
+        Board::generation(self)
+    }
+    fn population(&self) -> u64 {
+        Board::population(self)
+    }
+    fn bounding_box(&self) -> Option<(i64, i64, i64, i64)> {
+        Board::bounding_box(self)
+    }
+    fn cycle(&self) -> Option<Cycle> {
+        Board::cycle(self)
+    }
+    fn births_and_deaths(&self) -> (u64, u64) {
+        Board::births_and_deaths(self)
+    }
+    fn state_hash(&self) -> u64 {
+        Board::state_hash(self)
+    }
+}
+impl Universe for SparseBoard {
+    fn step(&mut self) {
+        SparseBoard::step(self)
+    }
+    fn show_board(&self) {
+        SparseBoard::show_board(self)
+    }
+    fn generation(&self) -> u64 {
+        SparseBoard::generation(self)
+    }
+    fn population(&self) -> u64 {
+        SparseBoard::population(self)
+    }
+    fn bounding_box(&self) -> Option<(i64, i64, i64, i64)> {
+        SparseBoard::bounding_box(self)
+    }
+    fn cycle(&self) -> Option<Cycle> {
+        SparseBoard::cycle(self)
+    }
+    fn births_and_deaths(&self) -> (u64, u64) {
+        SparseBoard::births_and_deaths(self)
+    }
+    fn state_hash(&self) -> u64 {
+        SparseBoard::state_hash(self)
+    }
+}
+impl Universe for BitBoard {
+    fn step(&mut self) {
+        BitBoard::step(self)
+    }
+    fn show_board(&self) {
+        BitBoard::show_board(self)
+    }
+    fn generation(&self) -> u64 {
+        BitBoard::generation(self)
+    }
+    fn population(&self) -> u64 {
+        BitBoard::population(self)
+    }
+    fn bounding_box(&self) -> Option<(i64, i64, i64, i64)> {
+        BitBoard::bounding_box(self)
+    }
+    fn cycle(&self) -> Option<Cycle> {
+        BitBoard::cycle(self)
+    }
+    fn births_and_deaths(&self) -> (u64, u64) {
+        BitBoard::births_and_deaths(self)
+    }
+    fn state_hash(&self) -> u64 {
+        BitBoard::state_hash(self)
+    }
+}